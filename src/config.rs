@@ -0,0 +1,369 @@
+//! Settings for the whole backend, read from a TOML file.
+//!
+//! Any value can be overridden with an environment variable named
+//! `GYM_<SECTION>__<KEY>`, e.g. `GYM_SLEEPER__FREQUENCY=600` or
+//! `GYM_STORAGE__LOCAL_STORE=local_store.json`.
+
+use std::{collections::HashSet, env, error::Error, fmt, fs, io};
+
+use reqwest::Url;
+use serde::Deserialize;
+use toml::{Table, Value};
+
+use crate::{facility::Facility, firebase::credentials::CredentialSource, knn_regressor::metric::Metric};
+
+/// Read unless `GYM_CONFIG` names another file.
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+const ENV_PREFIX: &str = "GYM_";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read { path: String, err: io::Error },
+    Parse(toml::de::Error),
+    /// An override variable does not fit the config's shape.
+    Env { name: String, reason: String },
+    /// The config parses but makes no sense, e.g. `k = 0`.
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read { path, err } => write!(f, "Could not read config {}: {}", path, err),
+            Self::Parse(err) => write!(f, "Invalid config: {}", err),
+            Self::Env { name, reason } => write!(f, "Invalid override {}: {}", name, reason),
+            Self::Invalid(reason) => write!(f, "Invalid config: {}", reason),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Read { err, .. } => Some(err),
+            Self::Parse(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub scraper: ScraperConfig,
+    #[serde(default)]
+    pub firebase: FirebaseConfig,
+    #[serde(default)]
+    pub sleeper: SleeperConfig,
+    #[serde(default)]
+    pub regressor: RegressorConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScraperConfig {
+    #[serde(default = "default_user_agent")]
+    pub user_agent: String,
+    /// Save every fetched page under this directory, see `fetcher::Recorder`.
+    #[serde(default)]
+    pub record_dir: Option<String>,
+    /// `[[scraper.facility]]` tables.
+    #[serde(rename = "facility")]
+    pub facilities: Vec<Facility>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FirebaseConfig {
+    /// e.g. `https://<project>.firebaseio.com/`. Read from `database_url_file` when unset.
+    pub database_url: Option<String>,
+    pub database_url_file: String,
+    pub credentials: CredentialSource,
+    pub service_account_file: String,
+    /// `host:port` of the Realtime Database emulator. Skips OAuth.
+    /// `FIREBASE_DATABASE_EMULATOR_HOST` also sets it, as with Google's tools.
+    pub emulator_host: Option<String>,
+    /// Database name sent to the emulator.
+    pub namespace: String,
+}
+
+impl Default for FirebaseConfig {
+    fn default() -> Self {
+        Self {
+            database_url: None,
+            database_url_file: "databaseUrl.secret".to_string(),
+            credentials: CredentialSource::File,
+            service_account_file: "serviceAccountKey.json.secret".to_string(),
+            emulator_host: None,
+            namespace: "gym-backend".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SleeperConfig {
+    /// Seconds between samples. Samples are aligned to the hour.
+    pub frequency: u64,
+    /// Seconds to wait after a failed scrape.
+    pub error_wait: u64,
+}
+
+impl Default for SleeperConfig {
+    fn default() -> Self {
+        Self {
+            frequency: 5 * 60,
+            error_wait: 5 * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegressorConfig {
+    /// Weeks of history, and neighbours, used for each prediction.
+    pub k: usize,
+}
+
+impl Default for RegressorConfig {
+    fn default() -> Self {
+        // SQRT((15 * 60) / 5) -> Sqrt of number of data points
+        // 8.9
+        Self { k: 3 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Keep the data in this JSON file instead of Firebase.
+    pub local_store: Option<String>,
+    /// Directory for the outbox journals and KNN caches.
+    pub state_dir: String,
+    /// Keep every scraped page, see `web_scraper::archive`.
+    pub archive_pages: bool,
+    /// Pages go to `<page_archive_dir>/<facility name>`.
+    pub page_archive_dir: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            local_store: None,
+            state_dir: ".".to_string(),
+            archive_pages: true,
+            page_archive_dir: "pages".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Weeks kept in the database, on top of the current one.
+    pub weeks: usize,
+    pub archive_dir: String,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            weeks: 12,
+            archive_dir: "archive".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertsConfig {
+    /// Consecutive failures of a field before alerting.
+    pub threshold: u32,
+    pub stdout: bool,
+    /// Receives a JSON POST per alert, e.g. a Slack incoming webhook.
+    pub webhook_url: Option<String>,
+    pub smtp: Option<SmtpConfig>,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 3,
+            stdout: true,
+            webhook_url: None,
+            smtp: None,
+        }
+    }
+}
+
+/// A server that needs neither TLS nor a login, e.g. a local relay.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmtpConfig {
+    #[serde(default = "default_smtp_host")]
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+fn default_smtp_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_smtp_port() -> u16 {
+    25
+}
+
+fn default_user_agent() -> String {
+    "Mozilla/5.0".to_string()
+}
+
+impl Config {
+    /// Reads the file at `GYM_CONFIG`, or `config.toml`.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = env::var("GYM_CONFIG").unwrap_or(DEFAULT_CONFIG_PATH.to_string());
+        let contents = fs::read_to_string(&path).map_err(|err| ConfigError::Read { path, err })?;
+        Self::from_toml(&contents, env::vars())
+    }
+
+    /// Parses `contents`, applies the `GYM_` overrides among `vars` and validates the result.
+    pub fn from_toml(
+        contents: &str,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let mut table: Table = toml::from_str(contents).map_err(ConfigError::Parse)?;
+        for (name, value) in vars {
+            if name == "FIREBASE_DATABASE_EMULATOR_HOST" {
+                Self::apply_override(&mut table, &name, &["firebase", "emulator_host"], &value)?;
+            } else if let Some(path) = name.strip_prefix(ENV_PREFIX) {
+                if name == "GYM_CONFIG" {
+                    continue;
+                }
+                let path: Vec<String> = path.split("__").map(|key| key.to_lowercase()).collect();
+                let path: Vec<&str> = path.iter().map(|key| key.as_str()).collect();
+                Self::apply_override(&mut table, &name, &path, &value)?;
+            }
+        }
+
+        let mut config: Self = table.try_into().map_err(ConfigError::Parse)?;
+        for facility in config.scraper.facilities.iter_mut() {
+            facility.prefix = facility.prefix.trim_matches('/').to_string();
+            facility.state_dir = config.storage.state_dir.clone();
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_override(table: &mut Table, name: &str, path: &[&str], raw: &str) -> Result<(), ConfigError> {
+        let env_error = |reason: &str| ConfigError::Env {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        };
+        let (key, sections) = match path.split_last() {
+            Some((key, sections)) if !key.is_empty() && !sections.is_empty() => (key, sections),
+            _ => return Err(env_error("expected GYM_<SECTION>__<KEY>")),
+        };
+        let mut table = table;
+        for section in sections {
+            let entry = table
+                .entry(section.to_string())
+                .or_insert_with(|| Value::Table(Table::new()));
+            table = match entry {
+                Value::Table(table) => table,
+                _ => return Err(env_error(&format!("`{}` is not a section", section))),
+            };
+        }
+        // Numbers and booleans are parsed as such, anything else is a string
+        let value = toml::from_str::<Table>(&format!("value = {}", raw))
+            .ok()
+            .and_then(|mut parsed| parsed.remove("value"))
+            .unwrap_or(Value::String(raw.to_string()));
+        table.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: String| Err(ConfigError::Invalid(reason));
+
+        if self.scraper.facilities.is_empty() {
+            return invalid("no [[scraper.facility]] configured".to_string());
+        }
+        let mut names = HashSet::new();
+        let mut prefixes = HashSet::new();
+        for facility in &self.scraper.facilities {
+            if facility.name.is_empty() || facility.name.contains(|c: char| c.is_whitespace() || c == '/') {
+                return invalid(format!("facility name {:?} must be one word", facility.name));
+            }
+            if !names.insert(&facility.name) {
+                return invalid(format!("facility {} is listed twice", facility.name));
+            }
+            if facility.prefix.is_empty() {
+                return invalid(format!("facility {} has an empty prefix", facility.name));
+            }
+            if !prefixes.insert(&facility.prefix) {
+                return invalid(format!("prefix {} is used by two facilities", facility.prefix));
+            }
+            for url in std::iter::once(&facility.url).chain(&facility.schedule_url) {
+                if let Err(err) = Url::parse(url) {
+                    return invalid(format!("facility {}: bad URL {:?}: {}", facility.name, url, err));
+                }
+            }
+            if let Err(err) = facility.selectors.compile() {
+                return invalid(format!("facility {}: {}", facility.name, err));
+            }
+            if facility.selectors.headcount.is_none() {
+                if facility.selectors.capacity.is_some() {
+                    return invalid(format!("facility {}: a capacity selector needs a headcount selector", facility.name));
+                }
+                if facility.predict == Metric::Headcount {
+                    return invalid(format!("facility {}: predicting the headcount needs a headcount selector", facility.name));
+                }
+            }
+        }
+
+        let frequency = self.sleeper.frequency;
+        if frequency < 60 || !frequency.is_multiple_of(60) || !3600u64.is_multiple_of(frequency) {
+            return invalid(format!(
+                "sleeper.frequency must be a whole number of minutes dividing an hour, not {}s",
+                frequency
+            ));
+        }
+        if self.sleeper.error_wait == 0 {
+            return invalid("sleeper.error_wait must be positive".to_string());
+        }
+        if self.regressor.k == 0 {
+            return invalid("regressor.k must be at least 1".to_string());
+        }
+        if self.retention.weeks == 0 {
+            return invalid("retention.weeks must be at least 1".to_string());
+        }
+        if self.alerts.threshold == 0 {
+            return invalid("alerts.threshold must be at least 1".to_string());
+        }
+        if let Some(url) = &self.alerts.webhook_url {
+            if let Err(err) = Url::parse(url) {
+                return invalid(format!("alerts.webhook_url: {}", err));
+            }
+        }
+        if let Some(smtp) = &self.alerts.smtp {
+            if smtp.to.is_empty() {
+                return invalid("alerts.smtp.to needs at least one address".to_string());
+            }
+        }
+        if let Some(url) = &self.firebase.database_url {
+            if let Err(err) = Url::parse(url) {
+                return invalid(format!("firebase.database_url {:?}: {}", url, err));
+            }
+        }
+        Ok(())
+    }
+}
@@ -12,7 +12,7 @@ pub async fn error_logger(message: &str) {
         .open(format!("{}.log", file_name))
         .await
         .unwrap();
-    file.write(time.as_bytes()).await.unwrap();
-    file.write(message.as_bytes()).await.unwrap();
-    file.write("\n".as_bytes()).await.unwrap();
+    file.write_all(time.as_bytes()).await.unwrap();
+    file.write_all(message.as_bytes()).await.unwrap();
+    file.write_all("\n".as_bytes()).await.unwrap();
 }
@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{knn_regressor::metric::Metric, web_scraper::selectors::Selectors};
+
+/// Something with an occupancy and opening hours to scrape, e.g. the gym or the pool.
+///
+/// Each facility has its own page, selectors and schedule page, its own
+/// subtree of the database and its own local files.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Facility {
+    pub name: String,
+    /// Database location everything for this facility lives under, e.g. `rs_data`.
+    pub prefix: String,
+    /// Page showing the occupancy.
+    pub url: String,
+    /// Page listing the opening hours, when they are not on `url`.
+    #[serde(default)]
+    pub schedule_url: Option<String>,
+    #[serde(default)]
+    pub selectors: Selectors,
+    /// What the KNN regressor predicts.
+    #[serde(default)]
+    pub predict: Metric,
+    /// Where local files (KNN cache, outbox) are kept. Set from `[storage]`.
+    #[serde(skip)]
+    pub state_dir: String,
+}
+
+impl Facility {
+    /// Occupancy history, schedules and `latest`.
+    pub fn data_location(&self) -> String {
+        format!("{}/data", self.prefix)
+    }
+
+    /// Number of people, by week like the occupancy.
+    pub fn headcount_location(&self) -> String {
+        format!("{}/headcount", self.data_location())
+    }
+
+    /// Capacity at each headcount sample.
+    pub fn capacity_location(&self) -> String {
+        format!("{}/capacity", self.data_location())
+    }
+
+    /// History of the predicted metric, which the KNN regressor learns from.
+    pub fn history_location(&self) -> String {
+        match self.predict {
+            Metric::Occupancy => self.data_location(),
+            Metric::Headcount => self.headcount_location(),
+        }
+    }
+
+    pub fn prediction_location(&self) -> String {
+        match self.predict {
+            Metric::Occupancy => format!("{}/prediction", self.prefix),
+            Metric::Headcount => format!("{}/prediction/headcount", self.prefix),
+        }
+    }
+
+    /// Log of changes to the published hours, see `ScheduleChange`.
+    pub fn schedule_changes_location(&self) -> String {
+        format!("{}/schedule_changes", self.prefix)
+    }
+
+    /// Cached KNN data for this week's predictions.
+    pub fn knn_data_path(&self) -> String {
+        self.knn_file("knn_regressor.data")
+    }
+
+    /// Cached KNN data for next Monday's predictions.
+    pub fn knn_tomorrow_data_path(&self) -> String {
+        self.knn_file("knn_regressor_tomorrow.data")
+    }
+
+    pub fn outbox_path(&self) -> String {
+        self.state_file("outbox.journal")
+    }
+
+    /// Kept apart per metric, so changing `predict` does not reuse the other's cache.
+    fn knn_file(&self, file: &str) -> String {
+        match self.predict {
+            Metric::Occupancy => self.state_file(file),
+            Metric::Headcount => self.state_file(&format!("headcount_{}", file)),
+        }
+    }
+
+    fn state_file(&self, file: &str) -> String {
+        Path::new(&self.state_dir)
+            .join(format!("{}_{}", self.name, file))
+            .to_string_lossy()
+            .to_string()
+    }
+}
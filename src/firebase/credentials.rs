@@ -0,0 +1,68 @@
+use std::{env, fmt};
+
+use serde::Deserialize;
+
+use super::error::FirebaseError;
+
+/// How the client proves who it is to the database.
+pub enum Credentials {
+    /// A service account key file, exchanged for OAuth2 access tokens.
+    ServiceAccountFile(String),
+    /// The contents of a service account key file, e.g. from a container secret.
+    ServiceAccountJson(String),
+    /// A pre-issued OAuth2 access token. It is never refreshed.
+    AccessToken(String),
+    /// A legacy database secret, sent as the `auth` query parameter.
+    DatabaseSecret(String),
+}
+
+/// Which `Credentials` to use, as named in the config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialSource {
+    /// The service account key file named in the config.
+    #[default]
+    File,
+    /// The key file at `GOOGLE_APPLICATION_CREDENTIALS`.
+    GoogleApplicationCredentials,
+    /// Inline key JSON in `FIREBASE_SERVICE_ACCOUNT_JSON`.
+    JsonEnv,
+    /// An access token in `FIREBASE_ACCESS_TOKEN`.
+    Token,
+    /// A database secret in `FIREBASE_DATABASE_SECRET`.
+    Secret,
+}
+
+impl Credentials {
+    /// Secrets themselves are only ever read from the environment, never the config file.
+    pub fn load(source: CredentialSource, service_account_file: &str) -> Result<Self, FirebaseError> {
+        match source {
+            CredentialSource::File => Ok(Self::ServiceAccountFile(service_account_file.to_string())),
+            CredentialSource::GoogleApplicationCredentials => {
+                Ok(Self::ServiceAccountFile(Self::var("GOOGLE_APPLICATION_CREDENTIALS")?))
+            }
+            CredentialSource::JsonEnv => Ok(Self::ServiceAccountJson(Self::var("FIREBASE_SERVICE_ACCOUNT_JSON")?)),
+            CredentialSource::Token => Ok(Self::AccessToken(Self::var("FIREBASE_ACCESS_TOKEN")?)),
+            CredentialSource::Secret => Ok(Self::DatabaseSecret(Self::var("FIREBASE_DATABASE_SECRET")?)),
+        }
+    }
+
+    fn var(name: &str) -> Result<String, FirebaseError> {
+        match env::var(name) {
+            Ok(value) if !value.trim().is_empty() => Ok(value),
+            _ => Err(FirebaseError::Credentials(format!("{} is not set", name))),
+        }
+    }
+}
+
+/// Never print the secrets.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ServiceAccountFile(path) => f.debug_tuple("ServiceAccountFile").field(path).finish(),
+            Self::ServiceAccountJson(_) => f.write_str("ServiceAccountJson(<redacted>)"),
+            Self::AccessToken(_) => f.write_str("AccessToken(<redacted>)"),
+            Self::DatabaseSecret(_) => f.write_str("DatabaseSecret(<redacted>)"),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::{error::Error, fmt, io};
+
+use reqwest::StatusCode;
+
+#[derive(Debug)]
+pub enum FirebaseError {
+    /// No usable credentials are configured.
+    Credentials(String),
+    /// The service key file could not be read.
+    KeyRead(io::Error),
+    /// The service key is not valid JSON or is missing fields.
+    KeyParse(String),
+    /// The JWT for the OAuth2 token exchange could not be signed.
+    JwtSigning(jsonwebtoken::errors::Error),
+    /// Google did not hand out an access token for our JWT.
+    TokenExchange(String),
+    /// The request never got a response (DNS, connection, timeout...).
+    Network(reqwest::Error),
+    /// The database answered with a non-success status.
+    HttpStatus(StatusCode),
+    /// The database rejected our credentials (401/403).
+    PermissionDenied(StatusCode),
+    /// A conditional write lost the race: the data no longer matches our ETag.
+    /// Carries the current ETag and data so the caller can retry without a read.
+    PreconditionFailed { etag: String, current: String },
+    /// The response body was not the JSON we expected.
+    JsonDecode(serde_json::Error),
+}
+
+impl FirebaseError {
+    /// Whether trying the same request again later could succeed.
+    /// Permission errors count, since an expired or revoked token is
+    /// replaced before the next request.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Network(_) | Self::TokenExchange(_) | Self::PermissionDenied(_) => true,
+            Self::HttpStatus(status) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for FirebaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Credentials(reason) => write!(f, "Invalid credentials: {}", reason),
+            Self::KeyRead(err) => write!(f, "Could not read service key: {}", err),
+            Self::KeyParse(reason) => write!(f, "Invalid service key: {}", reason),
+            Self::JwtSigning(err) => write!(f, "Could not construct JWT: {}", err),
+            Self::TokenExchange(reason) => write!(f, "Token exchange failed: {}", reason),
+            Self::Network(err) => write!(f, "Network error: {}", err),
+            Self::HttpStatus(status) => write!(f, "Unexpected HTTP status: {}", status),
+            Self::PermissionDenied(status) => write!(f, "Permission denied: {}", status),
+            Self::PreconditionFailed { etag, .. } => {
+                write!(f, "Data changed since it was read (now {})", etag)
+            }
+            Self::JsonDecode(err) => write!(f, "Unexpected JSON: {}", err),
+        }
+    }
+}
+
+impl Error for FirebaseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::KeyRead(err) => Some(err),
+            Self::JwtSigning(err) => Some(err),
+            Self::Network(err) => Some(err),
+            Self::JsonDecode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for FirebaseError {
+    fn from(err: reqwest::Error) -> Self {
+        // The URL may carry a database secret
+        Self::Network(err.without_url())
+    }
+}
+
+impl From<serde_json::Error> for FirebaseError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::JsonDecode(err)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for FirebaseError {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        Self::JwtSigning(err)
+    }
+}
@@ -1,190 +1,270 @@
-use std::fs;
+use std::{fmt, sync::Arc, time::Duration};
 
-use jsonwebtoken::{EncodingKey, encode, Header, Algorithm};
-use reqwest::{self, Client};
-use serde_json::{from_str, Value, json};
+use futures_util::Stream;
+use reqwest::{
+    self,
+    header::{ACCEPT, IF_MATCH},
+    Client, Method, RequestBuilder, Response,
+};
+use tokio::task::JoinHandle;
 
-use crate::core_functions::error_logger::error_logger;
+use crate::{
+    core_functions::error_logger::error_logger,
+    store::{backend::Store, query::Query},
+};
 
-use super::service_key::ServiceKey;
+use super::{
+    credentials::Credentials,
+    error::FirebaseError,
+    http::etag,
+    listener::{event_stream, Event},
+    retry::RetryPolicy,
+    token_manager::TokenManager,
+};
 
+/// Longest a request may take, so a hung connection fails and is retried
+/// rather than blocking the caller forever.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Streams stay open indefinitely, so only connecting is limited.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn client() -> Client {
+    Client::builder().timeout(REQUEST_TIMEOUT).build().expect("HTTP client to build")
+}
+
+fn stream_client() -> Client {
+    Client::builder().connect_timeout(CONNECT_TIMEOUT).build().expect("HTTP client to build")
+}
+
+/// Cheap to share between tasks as `Arc<Firebase>`: access tokens are
+/// managed internally and every method takes `&self`.
 pub struct Firebase {
     client: Client,
+    /// Without a request timeout, for `listen`.
+    stream_client: Client,
     db_url: String,
-    service_key: ServiceKey,
-    auth_token: Option<String>,
-    jwt: String,
-    exp: i64
+    tokens: Arc<TokenManager>,
+    retry_policy: RetryPolicy,
+    /// Database name, only sent to the emulator where the URL does not carry it.
+    namespace: Option<String>,
 }
 
 impl Firebase {
-    pub fn new(path_to_service_key: &str, db_url: String) -> Self {
-        let key_contents = fs::read_to_string(path_to_service_key).unwrap();
-        let key: Value = match from_str(&key_contents) {
-            Ok(key) => key,
-            Err(_) => {
-                println!("Error parsing key");
-                std::process::exit(1);
-            }
-        };
+    pub fn new(path_to_service_key: &str, db_url: String) -> Result<Self, FirebaseError> {
+        Self::with_credentials(Credentials::ServiceAccountFile(path_to_service_key.to_string()), db_url)
+    }
 
-        let service_key = match ServiceKey::new(&key) {
-            Some(key) => key,
-            None => {
-                println!("Key is missing fields.");
-                std::process::exit(1);
-            }
-        };
+    pub fn with_credentials(credentials: Credentials, db_url: String) -> Result<Self, FirebaseError> {
+        let client = client();
+        let retry_policy = RetryPolicy::default();
+        let tokens = TokenManager::new(client.clone(), credentials, retry_policy.clone())?;
+        tokens.validate()?;
 
-        let (jwt, exp) = match Self::construct_jwt(&service_key) {
-            Some(key) => key,
-            None => {
-                println!("Could not construct JWT. Check Key");
-                std::process::exit(1);
-            }
-        };
+        Ok(Self {
+            client,
+            stream_client: stream_client(),
+            db_url,
+            tokens: Arc::new(tokens),
+            retry_policy,
+            namespace: None,
+        })
+    }
 
+    /// Talks to the Realtime Database emulator (or `MockServer`) at `base_url`,
+    /// e.g. `http://127.0.0.1:9000/`. No OAuth: the emulator accepts `owner`
+    /// as an admin token.
+    pub fn emulator(base_url: &str, namespace: &str) -> Self {
+        let mut db_url = base_url.to_string();
+        if !db_url.ends_with('/') {
+            db_url.push('/');
+        }
         Self {
-            client: Client::new(),
+            client: client(),
+            stream_client: stream_client(),
             db_url,
-            service_key,
-            jwt,
-            auth_token: None,
-            exp
+            tokens: Arc::new(TokenManager::fixed("owner")),
+            retry_policy: RetryPolicy::default(),
+            namespace: Some(namespace.to_string()),
         }
     }
-    // Token + exp
-    fn construct_jwt(key: &ServiceKey) -> Option<(String, i64)> {
-        // Google OAuth2 Rest API
-        let encoding_key = EncodingKey::from_rsa_pem(key.private_key().as_bytes()).ok()?;
-        let iat = chrono::Utc::now().timestamp();
-        let exp = iat + 3600; // 1 Hour Exp
-        let token = encode(
-            &Header::new(Algorithm::RS256),
-            &json!({
-                "iss": key.email(),
-                "sub": key.email(),
-                "scope" : "https://www.googleapis.com/auth/firebase.database https://www.googleapis.com/auth/userinfo.email",
-                "aud": key.token_url(),
-                "iat": iat,
-                "exp": exp,
-            }),
-            &encoding_key
-        ).ok()?;
-        Some((token, exp))
-    }
-
-    fn set_jwt(&mut self) {
-        let (jwt, exp) = match Self::construct_jwt(&self.service_key) {
-            Some(key) => key,
-            None => {
-                println!("Could not construct JWT. Check Key");
-                std::process::exit(1);
-            }
-        };
-        self.jwt = jwt;
-        self.exp = exp;
-    }
-
-    async fn set_auth_token(&mut self) -> Result<(),()> {
-        let response = match self.client
-            .post(self.service_key.token_url())
-            .form(&[("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"), ("assertion", &self.jwt)])
-            .send()
-            .await {
-                Ok(res) => res,
-                Err(_) => {
-                    error_logger("Error generating token").await;
-                    return Err(());
-                }
-            };
 
-        let json_res: Value = from_str(
-            response
-                .text()
-                .await
-                .expect("Unexpected Error")
-                .as_str()
-        ).expect("Unexpected response json parse error");
-
-        // self.auth_token = Some(json_res["access_token"].as_str().expect("Unexpected error - unwrap on access token")
-        //     .to_string());
-        self.auth_token = match json_res["access_token"].as_str() {
-            Some(token) => Some(token.to_string()),
-            None => {
-                error_logger("Access Token as_str error").await;
-                dbg!(json_res);
-                None
-            }
-        };
-        if self.auth_token.is_none() {
-            return Err(());
+    /// Call before the client is shared.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        if let Some(tokens) = Arc::get_mut(&mut self.tokens) {
+            tokens.set_retry_policy(retry_policy.clone());
         }
-        Ok(())
+        self.retry_policy = retry_policy;
+        self
     }
 
-    pub async fn handle_auth_token(&mut self) -> Result<(),()> {
-        let now = chrono::Utc::now().timestamp();
-        if self.exp < now || self.auth_token.is_none() {
-            // Generate new jwt
-            self.set_jwt();
-            // get new key
-            if self.set_auth_token().await.is_err() {
-                return Err(());
-            }
+    /// Keeps the access token fresh in the background, so requests never wait
+    /// on a token exchange. Without it tokens are refreshed on demand.
+    pub fn spawn_token_refresher(&self) -> JoinHandle<()> {
+        self.tokens.spawn_refresher()
+    }
+
+    /// Makes sure a valid access token is available.
+    pub async fn handle_auth_token(&self) -> Result<(), FirebaseError> {
+        self.tokens.token().await.map(|_| ())
+    }
+
+    /// Streams changes under `location`. The first event is a `put` of the
+    /// current data at `/`. Listen again after `Event::AuthRevoked`.
+    pub async fn listen(
+        &self,
+        location: &str,
+    ) -> Result<impl Stream<Item = Result<Event, FirebaseError>>, FirebaseError> {
+        let request = self.request_with(&self.stream_client, Method::GET, location).await?
+            .header(ACCEPT, "text/event-stream");
+        let response = Self::log_error(self.send(request, location).await, "Listen").await?;
+        Ok(event_stream(response))
+    }
+
+    /// Access tokens go in a header rather than the URL, which ends up in logs
+    /// and errors. A legacy database secret can only be sent as `auth=` in the URL.
+    async fn request(&self, method: Method, location: &str) -> Result<RequestBuilder, FirebaseError> {
+        self.request_with(&self.client, method, location).await
+    }
+
+    async fn request_with(
+        &self,
+        client: &Client,
+        method: Method,
+        location: &str,
+    ) -> Result<RequestBuilder, FirebaseError> {
+        let request = client.request(method, format!("{}{}.json", self.db_url, location));
+        let mut request = self.tokens.authorise(request).await?;
+        if let Some(namespace) = &self.namespace {
+            request = request.query(&[("ns", namespace)]);
         }
+        Ok(request)
+    }
+
+    async fn send(&self, request: RequestBuilder, location: &str) -> Result<Response, FirebaseError> {
+        let result = self.retry_policy.send(request, location).await;
+        if let Err(FirebaseError::PermissionDenied(_)) = &result {
+            self.tokens.invalidate().await;
+        }
+        result
+    }
+
+    /// Returns the data at `location` with its ETag, for a later conditional write.
+    pub async fn get_with_etag(&self, location: &str) -> Result<(String, String), FirebaseError> {
+        let request = self.request(Method::GET, location).await?
+            .header("X-Firebase-ETag", "true");
+        let response = Self::log_error(self.send(request, location).await, "Get").await?;
+        let etag = etag(&response)?;
+        Ok((response.text().await?, etag))
+    }
+
+    /// Replaces the data at `location` only if it is unchanged since `etag` was read.
+    /// Fails with `FirebaseError::PreconditionFailed` otherwise.
+    pub async fn set_if_match(&self, location: &str, data: &str, etag: &str) -> Result<(), FirebaseError> {
+        let request = self.request(Method::PUT, location).await?
+            .header(IF_MATCH, etag)
+            .body(data.to_string());
+        self.send(request, location).await?;
         Ok(())
     }
 
-    pub async fn update(&self, location: String, data: &str) {
-        let auth_token = &self.auth_token.as_ref().unwrap();
+    /// Removes `location` only if it is unchanged since `etag` was read.
+    pub async fn delete_if_match(&self, location: &str, etag: &str) -> Result<(), FirebaseError> {
+        let request = self.request(Method::DELETE, location).await?
+            .header(IF_MATCH, etag);
+        self.send(request, location).await?;
+        Ok(())
+    }
 
-        let response = match self.client
-            .patch(format!("{}{}.json?access_token={}",self.db_url,location,auth_token))
-            .body(data.to_string())
-            .send()
-            .await {
-                Ok(data) => data,
-                Err(_) => {
-                    error_logger("Put error").await;
-                    return;
-                }
+    /// Atomically replaces the data at `location` with `update(current)`.
+    /// When another writer gets in first, `update` is run again on their data.
+    /// Returning `None` from `update` leaves the data alone.
+    /// Returns the data that ended up at `location`.
+    pub async fn compare_and_swap<F>(&self, location: &str, mut update: F) -> Result<String, FirebaseError>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let (mut current, mut etag) = self.get_with_etag(location).await?;
+        let mut attempt = 1;
+        loop {
+            let new = match update(&current) {
+                Some(new) => new,
+                None => return Ok(current),
             };
-        // dbg!(response.text().await.unwrap());
-        println!("{} {}", response.status(), location);
-    }
-
-    pub async fn set(&self, location: String, data: &str) {
-        let auth_token = &self.auth_token.as_ref().unwrap();
-
-        let response = match self.client
-            .put(format!("{}{}.json?access_token={}",self.db_url,location,auth_token))
-            .body(data.to_string())
-            .send()
-            .await {
-                Ok(data) => data,
-                Err(_) => {
-                    error_logger("Put error").await;
-                    return;
+            match self.set_if_match(location, &new, &etag).await {
+                Ok(()) => return Ok(new),
+                Err(FirebaseError::PreconditionFailed { etag: new_etag, current: new_current })
+                    if attempt < self.retry_policy.max_attempts =>
+                {
+                    println!("Conflict on {}. Retrying compare and swap.", location);
+                    current = new_current;
+                    etag = new_etag;
+                    attempt += 1;
                 }
-            };
-        // dbg!(response.text().await.unwrap());
-        println!("{} {}", response.status(), location);
-    }
-
-    pub async fn get(&self, location: String) -> Option<String> {
-        let auth_token = &self.auth_token.as_ref().unwrap();
-        let response = match self.client
-        .get(format!("{}{}.json?access_token={}&print=pretty&orderBy=\"$key\"",self.db_url, location, auth_token))
-            .send()
-            .await {
-                Ok(data) => data,
                 Err(err) => {
-                    error_logger("Get Error").await;
-                    println!("{}",err);
-                    return None;
+                    error_logger(&format!("Compare and swap error - {}", err)).await;
+                    return Err(err);
                 }
-            };
-        Some(response.text().await.unwrap().to_string())
+            }
+        }
+    }
+
+    async fn log_error<T>(result: Result<T, FirebaseError>, action: &str) -> Result<T, FirebaseError> {
+        if let Err(err) = &result {
+            error_logger(&format!("{} error - {}", action, err)).await;
+        }
+        result
+    }
+}
+
+impl fmt::Debug for Firebase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Firebase")
+            .field("db_url", &self.db_url)
+            .field("tokens", &self.tokens)
+            .field("retry_policy", &self.retry_policy)
+            .field("namespace", &self.namespace)
+            .finish()
+    }
+}
+
+impl Store for Firebase {
+    type Error = FirebaseError;
+
+    fn is_transient(err: &FirebaseError) -> bool {
+        err.is_transient()
+    }
+
+    async fn prepare(&self) -> Result<(), FirebaseError> {
+        self.handle_auth_token().await
+    }
+
+    async fn update(&self, location: &str, data: &str) -> Result<(), FirebaseError> {
+        let request = self.request(Method::PATCH, location).await?.body(data.to_string());
+        Self::log_error(self.send(request, location).await, "Patch").await?;
+        Ok(())
+    }
+
+    async fn set(&self, location: &str, data: &str) -> Result<(), FirebaseError> {
+        let request = self.request(Method::PUT, location).await?.body(data.to_string());
+        Self::log_error(self.send(request, location).await, "Put").await?;
+        Ok(())
+    }
+
+    async fn delete(&self, location: &str) -> Result<(), FirebaseError> {
+        let request = self.request(Method::DELETE, location).await?;
+        Self::log_error(self.send(request, location).await, "Delete").await?;
+        Ok(())
+    }
+
+    async fn get(&self, location: &str) -> Result<String, FirebaseError> {
+        let request = self.request(Method::GET, location).await?;
+        let response = Self::log_error(self.send(request, location).await, "Get").await?;
+        Ok(response.text().await?)
+    }
+
+    async fn query(&self, location: &str, query: &Query) -> Result<String, FirebaseError> {
+        let request = self.request(Method::GET, location).await?.query(&query.to_params());
+        let response = Self::log_error(self.send(request, location).await, "Query").await?;
+        Ok(response.text().await?)
     }
 }
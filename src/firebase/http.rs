@@ -0,0 +1,29 @@
+use reqwest::{header::ETAG, RequestBuilder, Response, StatusCode};
+
+use super::error::FirebaseError;
+
+/// Sends the request once, turning unsuccessful statuses into errors.
+pub async fn send_once(request: RequestBuilder, location: &str) -> Result<Response, FirebaseError> {
+    let response = request.send().await?;
+    let status = response.status();
+    println!("{} {}", status, location);
+    match status {
+        status if status.is_success() => Ok(response),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(FirebaseError::PermissionDenied(status)),
+        StatusCode::PRECONDITION_FAILED => {
+            let etag = etag(&response)?;
+            let current = response.text().await?;
+            Err(FirebaseError::PreconditionFailed { etag, current })
+        }
+        status => Err(FirebaseError::HttpStatus(status)),
+    }
+}
+
+pub fn etag(response: &Response) -> Result<String, FirebaseError> {
+    response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(|etag| etag.to_string())
+        .ok_or(FirebaseError::HttpStatus(response.status()))
+}
@@ -0,0 +1,193 @@
+use futures_util::{stream, Stream};
+use reqwest::Response;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::error::FirebaseError;
+
+/// An event from the Realtime Database streaming REST API.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// `data` replaces whatever is at `path` (relative to the listened location).
+    Put { path: String, data: Value },
+    /// The children of `data` are merged into `path`.
+    Patch { path: String, data: Value },
+    KeepAlive,
+    /// Security rules no longer allow reading the location. The stream ends.
+    Cancel,
+    /// The access token expired. Listen again with a fresh token.
+    AuthRevoked,
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    path: String,
+    data: Value,
+}
+
+impl Event {
+    fn parse(name: &str, data: &str) -> Result<Option<Self>, FirebaseError> {
+        let event = match name {
+            "put" => {
+                let payload: Payload = serde_json::from_str(data)?;
+                Self::Put { path: payload.path, data: payload.data }
+            }
+            "patch" => {
+                let payload: Payload = serde_json::from_str(data)?;
+                Self::Patch { path: payload.path, data: payload.data }
+            }
+            "keep-alive" => Self::KeepAlive,
+            "cancel" => Self::Cancel,
+            "auth_revoked" => Self::AuthRevoked,
+            // Unknown events are skipped, as the SSE spec requires
+            _ => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+
+    /// After these the server closes the connection.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Cancel | Self::AuthRevoked)
+    }
+}
+
+/// Splits a `text/event-stream` body into events.
+/// Bytes are buffered so characters split across chunks survive.
+#[derive(Default)]
+struct EventParser {
+    buffer: Vec<u8>,
+}
+
+impl EventParser {
+    fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    fn next_event(&mut self) -> Option<Result<Event, FirebaseError>> {
+        loop {
+            let end = self.buffer.windows(2).position(|window| window == b"\n\n")?;
+            let block: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            let block = String::from_utf8_lossy(&block[..end]);
+
+            let mut name = "message";
+            let mut data: Vec<&str> = Vec::new();
+            for line in block.lines() {
+                let (field, value) = line.split_once(':').unwrap_or((line, ""));
+                let value = value.strip_prefix(' ').unwrap_or(value);
+                match field {
+                    "event" => name = value,
+                    "data" => data.push(value),
+                    _ => {}
+                }
+            }
+            match Event::parse(name, &data.join("\n")) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Turns a streaming response into a `Stream` of events.
+/// The stream ends when the server closes the connection or after a terminal event.
+pub fn event_stream(response: Response) -> impl Stream<Item = Result<Event, FirebaseError>> {
+    let state = Some((response, EventParser::default()));
+    stream::unfold(state, |state| async move {
+        let (mut response, mut parser) = state?;
+        loop {
+            if let Some(event) = parser.next_event() {
+                let terminal = matches!(&event, Ok(event) if event.is_terminal());
+                let next = if terminal { None } else { Some((response, parser)) };
+                return Some((event, next));
+            }
+            match response.chunk().await {
+                Ok(Some(chunk)) => parser.push(&chunk),
+                Ok(None) => return None,
+                Err(err) => return Some((Err(err.into()), None)),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse(chunks: &[&str]) -> Vec<Event> {
+        let mut parser = EventParser::default();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            parser.push(chunk.as_bytes());
+            while let Some(event) = parser.next_event() {
+                events.push(event.unwrap());
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn puts_and_patches() {
+        let events = parse(&[
+            "event: put\ndata: {\"path\":\"/\",\"data\":{\"a\":1}}\n\n",
+            "event: patch\ndata: {\"path\":\"/b\",\"data\":{\"c\":2}}\n\n",
+        ]);
+        assert_eq!(
+            events,
+            [
+                Event::Put { path: "/".to_string(), data: json!({ "a": 1 }) },
+                Event::Patch { path: "/b".to_string(), data: json!({ "c": 2 }) },
+            ]
+        );
+    }
+
+    #[test]
+    fn data_may_span_several_lines() {
+        let events = parse(&["event: put\ndata: {\"path\":\"/\",\ndata: \"data\":[1,\ndata: 2]}\n\n"]);
+        assert_eq!(events, [Event::Put { path: "/".to_string(), data: json!([1, 2]) }]);
+    }
+
+    #[test]
+    fn chunks_may_split_lines_and_characters() {
+        let event = "event: put\ndata: {\"path\":\"/\",\"data\":\"caf\u{e9}\"}\n\n".as_bytes();
+        // Split inside `event:`, inside the two byte `é` and between the blank lines
+        let splits = [3, event.len() - 5, event.len() - 1];
+        let mut parser = EventParser::default();
+        let mut start = 0;
+        for end in splits {
+            parser.push(&event[start..end]);
+            assert!(parser.next_event().is_none());
+            start = end;
+        }
+        parser.push(&event[start..]);
+        let event = parser.next_event().unwrap().unwrap();
+        assert_eq!(event, Event::Put { path: "/".to_string(), data: json!("caf\u{e9}") });
+        assert!(parser.next_event().is_none());
+    }
+
+    #[test]
+    fn keep_alive_and_terminal_events() {
+        let events = parse(&[
+            "event: keep-alive\ndata: null\n\n",
+            "event: cancel\ndata: null\n\nevent: auth_revoked\ndata: credential is no longer valid\n\n",
+        ]);
+        assert_eq!(events, [Event::KeepAlive, Event::Cancel, Event::AuthRevoked]);
+        assert!(!events[0].is_terminal());
+        assert!(events[1].is_terminal() && events[2].is_terminal());
+    }
+
+    #[test]
+    fn unknown_events_and_comments_are_skipped() {
+        let events = parse(&[": comment\n\nevent: rules_changed\ndata: {}\n\nevent: keep-alive\ndata: null\n\n"]);
+        assert_eq!(events, [Event::KeepAlive]);
+    }
+
+    #[test]
+    fn bad_payloads_are_errors() {
+        let mut parser = EventParser::default();
+        parser.push(b"event: put\ndata: {\"path\":\n\n");
+        assert!(matches!(parser.next_event(), Some(Err(FirebaseError::JsonDecode(_)))));
+    }
+}
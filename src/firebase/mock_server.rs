@@ -0,0 +1,315 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    io,
+    net::SocketAddr,
+    sync::Arc,
+};
+
+use reqwest::{StatusCode, Url};
+use serde_json::{json, Map, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+    task::JoinHandle,
+};
+
+use crate::store::{query::Query, tree};
+
+/// An in-process stand-in for the Realtime Database REST API, for tests.
+///
+/// Supports GET (with queries and ETags), PUT, PATCH and DELETE with the
+/// same array-vs-object coercion as Firebase. Any bearer token is accepted.
+/// Plain HTML pages can be served too, to stand in for the gym website, and
+/// POSTs to other paths are kept, to stand in for a webhook or, once
+/// `issue_tokens` is called, the OAuth2 token endpoint.
+pub struct MockServer {
+    address: SocketAddr,
+    state: Arc<Mutex<State>>,
+    handle: JoinHandle<()>,
+}
+
+struct State {
+    root: Value,
+    pages: HashMap<String, String>,
+    posted: HashMap<String, Vec<String>>,
+    /// Token endpoints and the `expires_in` of the tokens they issue.
+    token_endpoints: HashMap<String, i64>,
+    /// Access tokens that are refused, as if revoked.
+    revoked: HashSet<String>,
+    requests: Vec<RecordedRequest>,
+}
+
+/// A request as the server saw it, to check what credentials were sent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    /// The `Authorization` header, if any.
+    pub authorization: Option<String>,
+}
+
+struct Request {
+    method: String,
+    url: Url,
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+struct Response {
+    status: StatusCode,
+    content_type: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+impl Response {
+    fn json(status: StatusCode, body: &Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: body.to_string(),
+        }
+    }
+
+    fn error(status: StatusCode, message: &str) -> Self {
+        Self::json(status, &json!({ "error": message }))
+    }
+}
+
+impl MockServer {
+    /// Listens on a random local port until dropped.
+    pub async fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State {
+            root: Value::Object(Map::new()),
+            pages: HashMap::new(),
+            posted: HashMap::new(),
+            token_endpoints: HashMap::new(),
+            revoked: HashSet::new(),
+            requests: Vec::new(),
+        }));
+        let accept_state = state.clone();
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(Self::serve(stream, accept_state.clone()));
+            }
+        });
+        Ok(Self {
+            address,
+            state,
+            handle,
+        })
+    }
+
+    /// Base URL to hand to `Firebase::emulator`.
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.address)
+    }
+
+    /// The data currently stored at `location`.
+    pub async fn data(&self, location: &str) -> Value {
+        let state = self.state.lock().await;
+        tree::get(&state.root, location).cloned().unwrap_or(Value::Null)
+    }
+
+    /// Serves `html` at `path` (e.g. `/gym`), for scraping.
+    pub async fn serve_page(&self, path: &str, html: &str) {
+        let mut state = self.state.lock().await;
+        state.pages.insert(path.to_string(), html.to_string());
+    }
+
+    /// Answers POSTs to `path` with a new access token lasting `expires_in`
+    /// seconds, as Google's token endpoint does. Exchanges are counted in `posted`.
+    pub async fn issue_tokens(&self, path: &str, expires_in: i64) {
+        let mut state = self.state.lock().await;
+        state.token_endpoints.insert(path.to_string(), expires_in);
+    }
+
+    /// Refuses `token` from now on with a 401.
+    pub async fn revoke_token(&self, token: &str) {
+        let mut state = self.state.lock().await;
+        state.revoked.insert(token.to_string());
+    }
+
+    /// Every request received so far, oldest first.
+    pub async fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().await.requests.clone()
+    }
+
+    /// Bodies POSTed to `path`, oldest first.
+    pub async fn posted(&self, path: &str) -> Vec<String> {
+        let state = self.state.lock().await;
+        state.posted.get(path).cloned().unwrap_or_default()
+    }
+
+    async fn serve(stream: TcpStream, state: Arc<Mutex<State>>) {
+        let mut stream = BufReader::new(stream);
+        let response = match Self::read_request(&mut stream).await {
+            Ok(request) => Self::handle(&mut *state.lock().await, request),
+            Err(_) => Response::error(StatusCode::BAD_REQUEST, "Malformed request"),
+        };
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            response.status.as_u16(),
+            response.status.canonical_reason().unwrap_or(""),
+            response.content_type,
+            response.body.len()
+        );
+        for (name, value) in &response.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        let stream = stream.get_mut();
+        let _ = stream.write_all(head.as_bytes()).await;
+        let _ = stream.write_all(response.body.as_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+
+    async fn read_request(stream: &mut BufReader<TcpStream>) -> io::Result<Request> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Malformed request");
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        let mut parts = line.split_whitespace();
+        let method = parts.next().ok_or_else(invalid)?.to_string();
+        let target = parts.next().ok_or_else(invalid)?;
+        let url = Url::parse(&format!("http://localhost{}", target)).map_err(|_| invalid())?;
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line.split_once(':').ok_or_else(invalid)?;
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+
+        let length: usize = headers
+            .get("content-length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await?;
+        Ok(Request {
+            method,
+            url,
+            headers,
+            body: String::from_utf8_lossy(&body).to_string(),
+        })
+    }
+
+    fn handle(state: &mut State, request: Request) -> Response {
+        let path = request.url.path().to_string();
+        state.requests.push(RecordedRequest {
+            method: request.method.clone(),
+            path: path.clone(),
+            query: request.url.query_pairs().into_owned().collect(),
+            authorization: request.headers.get("authorization").cloned(),
+        });
+        let location = match path.strip_suffix(".json") {
+            Some(location) => location,
+            None if request.method == "POST" => {
+                let posted = state.posted.entry(path.clone()).or_default();
+                posted.push(request.body);
+                let body = match state.token_endpoints.get(&path) {
+                    Some(expires_in) => json!({
+                        "access_token": format!("mock-token-{}", posted.len()),
+                        "expires_in": expires_in,
+                        "token_type": "Bearer",
+                    }),
+                    None => Value::Null,
+                };
+                return Response::json(StatusCode::OK, &body);
+            }
+            None => {
+                return match state.pages.get(&path) {
+                    Some(html) => Response {
+                        status: StatusCode::OK,
+                        content_type: "text/html",
+                        headers: Vec::new(),
+                        body: html.clone(),
+                    },
+                    None => Response::error(StatusCode::NOT_FOUND, "Not found"),
+                }
+            }
+        };
+
+        let params: Vec<(String, String)> = request.url.query_pairs().into_owned().collect();
+        let authorised = request.headers.contains_key("authorization")
+            || params.iter().any(|(name, _)| name == "auth");
+        let revoked = request
+            .headers
+            .get("authorization")
+            .and_then(|authorization| authorization.strip_prefix("Bearer "))
+            .is_some_and(|token| state.revoked.contains(token));
+        if !authorised || revoked {
+            return Response::error(StatusCode::UNAUTHORIZED, "Permission denied");
+        }
+
+        let current = tree::get(&state.root, location).cloned().unwrap_or(Value::Null);
+        let etag = Self::etag(&current);
+        if let Some(if_match) = request.headers.get("if-match") {
+            if *if_match != etag {
+                let mut response =
+                    Response::json(StatusCode::PRECONDITION_FAILED, &tree::coerce_arrays(&current));
+                response.headers.push(("ETag", etag));
+                return response;
+            }
+        }
+
+        let body = || serde_json::from_str::<Value>(&request.body);
+        match request.method.as_str() {
+            "GET" => {
+                let query = match Query::from_params(params.iter().map(|(n, v)| (n.as_str(), v.as_str()))) {
+                    Ok(query) => query,
+                    Err(message) => return Response::error(StatusCode::BAD_REQUEST, &message),
+                };
+                let mut response =
+                    Response::json(StatusCode::OK, &tree::coerce_arrays(&query.apply(&current)));
+                if request.headers.contains_key("x-firebase-etag") {
+                    response.headers.push(("ETag", etag));
+                }
+                response
+            }
+            "PUT" => match body() {
+                Ok(value) => {
+                    tree::set(&mut state.root, location, value.clone());
+                    Response::json(StatusCode::OK, &value)
+                }
+                Err(_) => Response::error(StatusCode::BAD_REQUEST, "Invalid data; couldn't parse JSON object"),
+            },
+            "PATCH" => match body() {
+                Ok(Value::Object(children)) => {
+                    let response = Response::json(StatusCode::OK, &Value::Object(children.clone()));
+                    tree::update(&mut state.root, location, children);
+                    response
+                }
+                _ => Response::error(StatusCode::BAD_REQUEST, "Invalid data; couldn't parse JSON object"),
+            },
+            "DELETE" => {
+                tree::delete(&mut state.root, location);
+                Response::json(StatusCode::OK, &Value::Null)
+            }
+            _ => Response::error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+        }
+    }
+
+    fn etag(value: &Value) -> String {
+        let mut hasher = DefaultHasher::new();
+        value.to_string().hash(&mut hasher);
+        format!("{:x}", hasher.finish())
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
@@ -1,2 +1,11 @@
+pub mod credentials;
+pub mod error;
+#[allow(clippy::module_inception)]
 pub mod firebase;
+mod http;
+pub mod listener;
+#[cfg(any(test, feature = "mock-server"))]
+pub mod mock_server;
+pub mod retry;
 mod service_key;
+pub mod token_manager;
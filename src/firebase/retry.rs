@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::{RequestBuilder, Response, StatusCode};
+
+use super::{error::FirebaseError, http::send_once};
+
+/// How failed REST calls are retried.
+/// Delays grow exponentially from `base_delay` up to `max_delay`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Randomise each delay between 0 and its exponential value (full jitter)
+    /// so that several instances do not retry in lockstep.
+    pub jitter: bool,
+    pub retry_on_server_error: bool,
+    pub retry_on_too_many_requests: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            retry_on_server_error: true,
+            retry_on_too_many_requests: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Fire once, never retry.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    pub fn should_retry(&self, error: &FirebaseError, attempt: u32) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        match error {
+            FirebaseError::Network(_) => true,
+            FirebaseError::HttpStatus(StatusCode::TOO_MANY_REQUESTS) => {
+                self.retry_on_too_many_requests
+            }
+            FirebaseError::HttpStatus(status) => {
+                status.is_server_error() && self.retry_on_server_error
+            }
+            _ => false,
+        }
+    }
+
+    /// Delay before the retry following the `attempt`th (1-based) attempt.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+        if !self.jitter {
+            return delay;
+        }
+        let millis = delay.as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=millis))
+    }
+
+    /// Sends the request, retrying transient failures.
+    pub async fn send(&self, request: RequestBuilder, location: &str) -> Result<Response, FirebaseError> {
+        let mut attempt = 1;
+        loop {
+            // Bodies are always in memory so the request can be cloned
+            let result = match request.try_clone() {
+                Some(request) => send_once(request, location).await,
+                None => return send_once(request, location).await,
+            };
+            match result {
+                Err(err) if self.should_retry(&err, attempt) => {
+                    let delay = self.delay(attempt);
+                    println!("{} {} - retrying in {}ms", err, location, delay.as_millis());
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use serde_json::Value;
+use std::{fmt, fs};
+
+use serde_json::{from_str, Value};
+
+use super::error::FirebaseError;
 
 
 pub struct ServiceKey {
@@ -19,6 +23,16 @@ impl ServiceKey {
         )
     }
 
+    pub fn from_file(path: &str) -> Result<Self, FirebaseError> {
+        let contents = fs::read_to_string(path).map_err(FirebaseError::KeyRead)?;
+        Self::from_json(&contents)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, FirebaseError> {
+        let key: Value = from_str(json).map_err(|err| FirebaseError::KeyParse(err.to_string()))?;
+        Self::new(&key).ok_or_else(|| FirebaseError::KeyParse("Key is missing fields".to_string()))
+    }
+
     pub fn email(&self) -> &String {
         &self.email
     }
@@ -30,3 +44,14 @@ impl ServiceKey {
         &self.token_url
     }
 }
+
+/// Never print the private key.
+impl fmt::Debug for ServiceKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceKey")
+            .field("email", &self.email)
+            .field("private_key", &"<redacted>")
+            .field("token_url", &self.token_url)
+            .finish()
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    fmt,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use reqwest::{Client, RequestBuilder};
+use serde_json::{from_str, json, Value};
+use tokio::{
+    sync::{Mutex, RwLock},
+    task::JoinHandle,
+};
+
+use crate::core_functions::error_logger::error_logger;
+
+use super::{credentials::Credentials, error::FirebaseError, retry::RetryPolicy, service_key::ServiceKey};
+
+/// Tokens are refreshed this long before they expire.
+const REFRESH_MARGIN: i64 = 5 * 60;
+/// Wait before trying again after a failed background refresh.
+const REFRESH_ERROR_WAIT: Duration = Duration::from_secs(30);
+
+struct AccessToken {
+    value: String,
+    expires_at: i64,
+}
+
+/// Where access tokens come from.
+enum TokenSource {
+    /// Exchanged for a signed JWT every hour.
+    ServiceAccount(ServiceKey),
+    /// Never expires, e.g. `owner` for the Realtime Database emulator.
+    Static(String),
+    /// Legacy database secret, sent as `auth=` rather than a bearer token.
+    DatabaseSecret(String),
+}
+
+/// Hands out OAuth2 access tokens for a service account and refreshes them
+/// ahead of expiry. Shared by every task using the same `Firebase`.
+/// Static tokens and database secrets are handed out as they are.
+pub struct TokenManager {
+    client: Client,
+    source: TokenSource,
+    retry_policy: RetryPolicy,
+    token: RwLock<Option<AccessToken>>,
+    /// Held while refreshing so concurrent callers wait for one exchange.
+    refreshing: Mutex<()>,
+}
+
+impl TokenManager {
+    pub(crate) fn new(client: Client, credentials: Credentials, retry_policy: RetryPolicy) -> Result<Self, FirebaseError> {
+        let source = match credentials {
+            Credentials::ServiceAccountFile(path) => TokenSource::ServiceAccount(ServiceKey::from_file(&path)?),
+            Credentials::ServiceAccountJson(json) => TokenSource::ServiceAccount(ServiceKey::from_json(&json)?),
+            Credentials::AccessToken(token) => TokenSource::Static(token),
+            Credentials::DatabaseSecret(secret) => TokenSource::DatabaseSecret(secret),
+        };
+        Ok(Self {
+            client,
+            source,
+            retry_policy,
+            token: RwLock::new(None),
+            refreshing: Mutex::new(()),
+        })
+    }
+
+    /// Always hands out `token`.
+    pub fn fixed(token: &str) -> Self {
+        Self {
+            client: Client::new(),
+            source: TokenSource::Static(token.to_string()),
+            retry_policy: RetryPolicy::default(),
+            token: RwLock::new(None),
+            refreshing: Mutex::new(()),
+        }
+    }
+
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Checks the key can sign a JWT, so a bad key is reported at start up.
+    pub fn validate(&self) -> Result<(), FirebaseError> {
+        match &self.source {
+            TokenSource::ServiceAccount(key) => Self::construct_jwt(key).map(|_| ()),
+            TokenSource::Static(_) | TokenSource::DatabaseSecret(_) => Ok(()),
+        }
+    }
+
+    /// Adds credentials to a request: `auth=` for a database secret,
+    /// otherwise a bearer token.
+    pub async fn authorise(&self, request: RequestBuilder) -> Result<RequestBuilder, FirebaseError> {
+        match &self.source {
+            TokenSource::DatabaseSecret(secret) => Ok(request.query(&[("auth", secret)])),
+            _ => Ok(request.bearer_auth(self.token().await?)),
+        }
+    }
+
+    /// A token that is valid for at least a few more minutes.
+    pub async fn token(&self) -> Result<String, FirebaseError> {
+        match &self.source {
+            TokenSource::Static(token) | TokenSource::DatabaseSecret(token) => return Ok(token.clone()),
+            TokenSource::ServiceAccount(_) => {}
+        }
+        if let Some(token) = self.fresh_token().await {
+            return Ok(token);
+        }
+        let _refreshing = self.refreshing.lock().await;
+        // Someone else may have refreshed while we waited
+        if let Some(token) = self.fresh_token().await {
+            return Ok(token);
+        }
+        match self.refresh().await {
+            Ok(token) => Ok(token),
+            Err(err) => {
+                // Keep going on the old token while it lasts
+                let now = chrono::Utc::now().timestamp();
+                match &*self.token.read().await {
+                    Some(token) if token.expires_at > now => Ok(token.value.clone()),
+                    _ => Err(err),
+                }
+            }
+        }
+    }
+
+    /// Forgets the current token after the database rejected it, so the next
+    /// request exchanges for a new one.
+    pub async fn invalidate(&self) {
+        *self.token.write().await = None;
+    }
+
+    /// Refreshes the token in the background shortly before it expires.
+    /// The task stops once the manager is dropped.
+    pub fn spawn_refresher(self: &Arc<Self>) -> JoinHandle<()> {
+        let manager: Weak<Self> = Arc::downgrade(self);
+        let refreshes = matches!(self.source, TokenSource::ServiceAccount(_));
+        tokio::spawn(async move {
+            if !refreshes {
+                return;
+            }
+            loop {
+                let wait = match manager.upgrade() {
+                    Some(manager) => manager.time_until_refresh().await,
+                    None => return,
+                };
+                tokio::time::sleep(wait).await;
+                let result = match manager.upgrade() {
+                    Some(manager) => {
+                        let _refreshing = manager.refreshing.lock().await;
+                        manager.refresh().await
+                    }
+                    None => return,
+                };
+                if let Err(err) = result {
+                    error_logger(&format!("Token Refresh Error - {}", err)).await;
+                    tokio::time::sleep(REFRESH_ERROR_WAIT).await;
+                }
+            }
+        })
+    }
+
+    async fn fresh_token(&self) -> Option<String> {
+        let now = chrono::Utc::now().timestamp();
+        match &*self.token.read().await {
+            Some(token) if token.expires_at - REFRESH_MARGIN > now => Some(token.value.clone()),
+            _ => None,
+        }
+    }
+
+    async fn time_until_refresh(&self) -> Duration {
+        let now = chrono::Utc::now().timestamp();
+        match &*self.token.read().await {
+            Some(token) => Duration::from_secs((token.expires_at - REFRESH_MARGIN - now).max(0) as u64),
+            None => Duration::ZERO,
+        }
+    }
+
+    // Token + exp
+    fn construct_jwt(key: &ServiceKey) -> Result<String, FirebaseError> {
+        // Google OAuth2 Rest API
+        let encoding_key = EncodingKey::from_rsa_pem(key.private_key().as_bytes())?;
+        let iat = chrono::Utc::now().timestamp();
+        let exp = iat + 3600; // 1 Hour Exp
+        let token = encode(
+            &Header::new(Algorithm::RS256),
+            &json!({
+                "iss": key.email(),
+                "sub": key.email(),
+                "scope" : "https://www.googleapis.com/auth/firebase.database https://www.googleapis.com/auth/userinfo.email",
+                "aud": key.token_url(),
+                "iat": iat,
+                "exp": exp,
+            }),
+            &encoding_key
+        )?;
+        Ok(token)
+    }
+
+    /// Exchanges a freshly signed JWT for an access token.
+    /// Callers must hold `refreshing`.
+    async fn refresh(&self) -> Result<String, FirebaseError> {
+        let key = match &self.source {
+            TokenSource::ServiceAccount(key) => key,
+            TokenSource::Static(token) | TokenSource::DatabaseSecret(token) => return Ok(token.clone()),
+        };
+        let jwt = Self::construct_jwt(key)?;
+        let request = self.client
+            .post(key.token_url())
+            .form(&[("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"), ("assertion", &jwt)]);
+        let response = self.retry_policy.send(request, "token").await.map_err(|err| match err {
+            FirebaseError::HttpStatus(status) | FirebaseError::PermissionDenied(status) => {
+                FirebaseError::TokenExchange(format!("Token endpoint answered {}", status))
+            }
+            err => err,
+        })?;
+
+        let status = response.status();
+        let json_res: Value = from_str(response.text().await?.as_str())?;
+
+        let value = json_res["access_token"]
+            .as_str()
+            .ok_or_else(|| FirebaseError::TokenExchange(format!("No access token in response ({})", status)))?
+            .to_string();
+        let expires_in = json_res["expires_in"].as_i64().unwrap_or(3600);
+        *self.token.write().await = Some(AccessToken {
+            value: value.clone(),
+            expires_at: chrono::Utc::now().timestamp() + expires_in,
+        });
+        Ok(value)
+    }
+}
+
+/// Never print the token.
+impl fmt::Debug for TokenManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match &self.source {
+            TokenSource::ServiceAccount(key) => format!("{:?}", key),
+            TokenSource::Static(_) => "Static(<redacted>)".to_string(),
+            TokenSource::DatabaseSecret(_) => "DatabaseSecret(<redacted>)".to_string(),
+        };
+        f.debug_struct("TokenManager")
+            .field("source", &source)
+            .field("token", &"<redacted>")
+            .finish()
+    }
+}
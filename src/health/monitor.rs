@@ -0,0 +1,139 @@
+use std::{collections::HashMap, fmt};
+
+use serde::Serialize;
+
+use crate::web_scraper::{error::ExtractError, headcount::Headcount};
+
+/// What the scraper reads from the page, watched separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    /// Fetching the page at all.
+    Page,
+    Occupancy,
+    Schedule,
+    Headcount,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Page => write!(f, "page"),
+            Self::Occupancy => write!(f, "occupancy"),
+            Self::Schedule => write!(f, "schedule"),
+            Self::Headcount => write!(f, "headcount"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// The field has failed `consecutive` times in a row.
+    Failing,
+    /// The field works again after `consecutive` failures.
+    Recovered,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub facility: String,
+    pub field: Field,
+    pub kind: AlertKind,
+    pub consecutive: u32,
+    /// The latest error, empty once recovered.
+    pub reason: String,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            AlertKind::Failing => write!(
+                f,
+                "{} {} has failed {} times in a row: {}",
+                self.facility, self.field, self.consecutive, self.reason
+            ),
+            AlertKind::Recovered => write!(
+                f,
+                "{} {} is working again after {} failures",
+                self.facility, self.field, self.consecutive
+            ),
+        }
+    }
+}
+
+/// An occupancy the page could show but the gym cannot have.
+pub fn check_occupancy(occupancy: u8) -> Result<u8, ExtractError> {
+    match occupancy {
+        0..=100 => Ok(occupancy),
+        _ => Err(ExtractError::Anomaly(format!("occupancy of {}%", occupancy))),
+    }
+}
+
+pub fn check_headcount(headcount: Option<Headcount>) -> Result<Option<Headcount>, ExtractError> {
+    match headcount {
+        Some(Headcount { capacity: Some(0), .. }) => Err(ExtractError::Anomaly("capacity of 0".to_string())),
+        Some(Headcount { people, capacity: Some(capacity) }) if people > capacity => {
+            Err(ExtractError::Anomaly(format!("headcount of {} of {}", people, capacity)))
+        }
+        _ => Ok(headcount),
+    }
+}
+
+#[derive(Debug, Default)]
+struct FieldHealth {
+    consecutive: u32,
+    alerted: bool,
+}
+
+/// Counts consecutive failures of each field of one facility and raises an
+/// alert once a field reaches `threshold`, and again when it recovers.
+/// Anomalies, e.g. an occupancy of 140%, count as failures.
+#[derive(Debug)]
+pub struct HealthMonitor {
+    facility: String,
+    threshold: u32,
+    fields: HashMap<Field, FieldHealth>,
+}
+
+impl HealthMonitor {
+    pub fn new(facility: &str, threshold: u32) -> Self {
+        Self {
+            facility: facility.to_string(),
+            threshold,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Records one attempt at `field`. Returns an alert when it crosses the threshold or recovers.
+    pub fn observe<T>(&mut self, field: Field, result: &Result<T, ExtractError>) -> Option<Alert> {
+        let health = self.fields.entry(field).or_default();
+        let alert = |kind, consecutive, reason: String| Alert {
+            facility: self.facility.clone(),
+            field,
+            kind,
+            consecutive,
+            reason,
+        };
+        match result {
+            Ok(_) => {
+                let recovered = health.alerted.then(|| alert(AlertKind::Recovered, health.consecutive, String::new()));
+                *health = FieldHealth::default();
+                recovered
+            }
+            Err(err) => {
+                health.consecutive += 1;
+                if health.alerted || health.consecutive < self.threshold {
+                    return None;
+                }
+                health.alerted = true;
+                Some(alert(AlertKind::Failing, health.consecutive, err.to_string()))
+            }
+        }
+    }
+
+    /// Consecutive failures of `field` so far.
+    pub fn failures(&self, field: Field) -> u32 {
+        self.fields.get(&field).map_or(0, |health| health.consecutive)
+    }
+}
@@ -0,0 +1,268 @@
+use std::{error::Error, fmt, future::Future, io, time::Duration};
+
+use reqwest::Client;
+use serde_json::json;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::timeout,
+};
+
+use crate::{config::AlertsConfig, core_functions::uk_datetime_now};
+
+use super::monitor::Alert;
+
+/// How long sending one alert may take, so a dead relay or webhook cannot
+/// hold up scraping.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum NotifyError {
+    Network(reqwest::Error),
+    /// The webhook answered with an error status.
+    Status(u16),
+    Io(io::Error),
+    /// The SMTP server refused a command.
+    Smtp(String),
+    /// Sending took longer than allowed.
+    Timeout(Duration),
+}
+
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Network(err) => write!(f, "Network error: {}", err),
+            Self::Status(status) => write!(f, "Webhook answered {}", status),
+            Self::Io(err) => write!(f, "IO error: {}", err),
+            Self::Smtp(reply) => write!(f, "SMTP server replied {:?}", reply),
+            Self::Timeout(limit) => write!(f, "Timed out after {:?}", limit),
+        }
+    }
+}
+
+impl Error for NotifyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Network(err) => Some(err),
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for NotifyError {
+    fn from(err: reqwest::Error) -> Self {
+        // Webhook URLs often embed a secret
+        Self::Network(err.without_url())
+    }
+}
+
+impl From<io::Error> for NotifyError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Somewhere to send alerts.
+pub trait Notifier {
+    fn notify(&self, alert: &Alert) -> impl Future<Output = Result<(), NotifyError>> + Send;
+}
+
+/// Prints alerts, for development or when the logs are watched anyway.
+#[derive(Debug, Clone, Default)]
+pub struct StdoutNotifier;
+
+impl Notifier for StdoutNotifier {
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        println!("ALERT - {}", alert);
+        Ok(())
+    }
+}
+
+/// POSTs `{"text": <message>, "alert": <alert>}`, which Slack-style
+/// incoming webhooks display as is.
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: Self::client(TIMEOUT),
+            url,
+        }
+    }
+
+    /// Gives up on the request after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = Self::client(timeout);
+        self
+    }
+
+    fn client(timeout: Duration) -> Client {
+        Client::builder().timeout(timeout).build().expect("HTTP client to build")
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        let response = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(json!({ "text": alert.to_string(), "alert": alert }).to_string())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(NotifyError::Status(response.status().as_u16()));
+        }
+        Ok(())
+    }
+}
+
+/// Sends a plain text email through an SMTP server that needs neither
+/// TLS nor a login, e.g. a local relay.
+#[derive(Debug, Clone)]
+pub struct SmtpNotifier {
+    address: String,
+    from: String,
+    to: Vec<String>,
+    timeout: Duration,
+}
+
+impl SmtpNotifier {
+    /// `address` is `host:port`.
+    pub fn new(address: String, from: String, to: Vec<String>) -> Self {
+        Self {
+            address,
+            from,
+            to,
+            timeout: TIMEOUT,
+        }
+    }
+
+    /// Gives up on the whole exchange after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn command(
+        stream: &mut BufReader<TcpStream>,
+        command: Option<&str>,
+        expected: &str,
+    ) -> Result<(), NotifyError> {
+        if let Some(command) = command {
+            stream.get_mut().write_all(format!("{}\r\n", command).as_bytes()).await?;
+        }
+        // Multi-line replies have a '-' after the code on all but the last line
+        loop {
+            let mut reply = String::new();
+            if stream.read_line(&mut reply).await? == 0 {
+                return Err(NotifyError::Smtp("connection closed".to_string()));
+            }
+            if !reply.starts_with(expected) {
+                return Err(NotifyError::Smtp(reply.trim_end().to_string()));
+            }
+            if reply.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), NotifyError> {
+        let mut stream = BufReader::new(TcpStream::connect(&self.address).await?);
+        Self::command(&mut stream, None, "220").await?;
+        Self::command(&mut stream, Some("HELO gym-backend"), "250").await?;
+        Self::command(&mut stream, Some(&format!("MAIL FROM:<{}>", self.from)), "250").await?;
+        for to in &self.to {
+            Self::command(&mut stream, Some(&format!("RCPT TO:<{}>", to)), "25").await?;
+        }
+        Self::command(&mut stream, Some("DATA"), "354").await?;
+
+        let body = alert.to_string();
+        let message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: [gym-backend] {} {}\r\nDate: {}\r\n\r\n{}\r\n.",
+            self.from,
+            self.to.join(", "),
+            alert.facility,
+            alert.field,
+            uk_datetime_now::now().to_rfc2822(),
+            // A lone '.' would end the message early
+            body.replace("\n.", "\n..")
+        );
+        Self::command(&mut stream, Some(&message), "250").await?;
+        Self::command(&mut stream, Some("QUIT"), "221").await
+    }
+}
+
+impl Notifier for SmtpNotifier {
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        timeout(self.timeout, self.send(alert))
+            .await
+            .map_err(|_| NotifyError::Timeout(self.timeout))?
+    }
+}
+
+/// One of the notifiers above, so a configured set can live in one list.
+#[derive(Debug, Clone)]
+pub enum AnyNotifier {
+    Stdout(StdoutNotifier),
+    Webhook(WebhookNotifier),
+    Smtp(SmtpNotifier),
+}
+
+impl Notifier for AnyNotifier {
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        match self {
+            Self::Stdout(notifier) => notifier.notify(alert).await,
+            Self::Webhook(notifier) => notifier.notify(alert).await,
+            Self::Smtp(notifier) => notifier.notify(alert).await,
+        }
+    }
+}
+
+/// Every notifier in `[alerts]`. Each is tried even if another fails.
+#[derive(Debug, Clone, Default)]
+pub struct Notifiers {
+    notifiers: Vec<AnyNotifier>,
+}
+
+impl Notifiers {
+    pub fn new(notifiers: Vec<AnyNotifier>) -> Self {
+        Self { notifiers }
+    }
+
+    pub fn from_config(config: &AlertsConfig) -> Self {
+        let mut notifiers = Vec::new();
+        if config.stdout {
+            notifiers.push(AnyNotifier::Stdout(StdoutNotifier));
+        }
+        if let Some(url) = &config.webhook_url {
+            notifiers.push(AnyNotifier::Webhook(WebhookNotifier::new(url.clone())));
+        }
+        if let Some(smtp) = &config.smtp {
+            notifiers.push(AnyNotifier::Smtp(SmtpNotifier::new(
+                format!("{}:{}", smtp.host, smtp.port),
+                smtp.from.clone(),
+                smtp.to.clone(),
+            )));
+        }
+        Self { notifiers }
+    }
+}
+
+impl Notifier for Notifiers {
+    /// Returns the last failure, if any.
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        let mut result = Ok(());
+        for notifier in &self.notifiers {
+            if let Err(err) = notifier.notify(alert).await {
+                result = Err(err);
+            }
+        }
+        result
+    }
+}
@@ -1,11 +1,13 @@
+use std::{collections::HashMap, ops::RangeInclusive};
+
 use chrono::{Duration, NaiveDate};
 use serde::{Deserialize, Serialize};
 use serde_json::{self, Value};
 use tokio::fs;
 
 use crate::{
-    core_functions::{error_logger::error_logger, get_start_of_week},
-    firebase::firebase::Firebase,
+    core_functions::get_start_of_week,
+    store::{backend::Store, query::Query},
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,95 +32,115 @@ impl DataPoint {
     }
 
     pub fn get_time(&self) -> u16 {
-        return self.time;
+        self.time
     }
 
     pub fn get_time_mut(&mut self) -> u16 {
-        return self.time;
+        self.time
     }
 
     pub fn get_occupancy(&self) -> u16 {
-        return self.occupancy;
+        self.occupancy
     }
 
     pub fn get_occupancy_mut(&mut self) -> u16 {
-        return self.occupancy;
+        self.occupancy
     }
 }
 
 impl Data {
     pub async fn from_file(path: &str) -> Option<Self> {
         let data = fs::read_to_string(path).await.ok()?;
-        Some(serde_json::from_str(&data).ok()?)
+        serde_json::from_str(&data).ok()
     }
 
     pub async fn write_to_file(&self, path: &str) {
         let _ = fs::write(path, serde_json::to_string(&self).unwrap()).await;
     }
 
-    pub async fn new(firebase: &Firebase, k: usize, date: NaiveDate) -> Self {
+    /// `location` holds the occupancy history by week, e.g. `rs_data/data`.
+    pub async fn new<S: Store>(store: &S, location: &str, k: usize, date: NaiveDate) -> Result<Self, S::Error> {
+        Self::new_for_days(store, location, k, date, 0..=6).await
+    }
+
+    /// Only fetches the weekdays in `days` (0 = Monday). Other days are left empty.
+    pub async fn new_for_days<S: Store>(
+        store: &S,
+        location: &str,
+        k: usize,
+        date: NaiveDate,
+        days: RangeInclusive<usize>,
+    ) -> Result<Self, S::Error> {
         let mut data = std::array::from_fn(|_| Vec::new());
+        let query = Query::new()
+            .order_by_key()
+            .start_at(days.start().to_string())
+            .end_at(days.end().to_string());
         for week in 1..k + 1 {
             // Get the week start date as keys
             let week_date: NaiveDate = date - Duration::days(7 * week as i64);
             let week_date = get_start_of_week::get(week_date);
             let key = week_date.to_string();
 
-            let fetch = firebase.get(format!("rs_data/data/{}", key)).await.unwrap();
-            let json_data: Value = serde_json::from_str(&fetch).unwrap();
+            let fetch = store.query(&format!("{}/{}", location, key), &query).await?;
+            let json_data: Value = serde_json::from_str(&fetch)?;
 
             if json_data.is_array() {
-                Self::handle_array(&mut data, json_data, k);
+                Self::handle_array(&mut data, json_data)?;
             } else if json_data.is_object() {
-                Self::handle_object(&mut data, json_data);
+                Self::handle_object(&mut data, json_data)?;
             } else {
-                error_logger("Unexpected Error - Unexpected type of response from Firebase").await;
-                std::process::exit(1);
+                // Nothing recorded that week
+                for day in data.iter_mut() {
+                    day.push(Vec::new());
+                }
             }
         }
-        Self {
+        Ok(Self {
             data,
             for_date: get_start_of_week::get(date).to_string(),
-        }
+        })
     }
 
     /// JSON Objects with consecutive number keys are treated as arrays
     /// Hence, it is handled differently.
-    fn handle_array(data: &mut [Vec<Vec<DataPoint>>; 7], json_data: Value, k: usize) {
-        for i in 0..7 {
+    fn handle_array(data: &mut [Vec<Vec<DataPoint>>; 7], json_data: Value) -> Result<(), serde_json::Error> {
+        for (i, day) in data.iter_mut().enumerate() {
             let new_data = match json_data.get(i) {
-                Some(day_data) => Self::get_vec_from_day(day_data),
+                Some(day_data) => Self::get_vec_from_day(day_data)?,
                 None => Vec::new(),
             };
-            data[i].push(new_data);
+            day.push(new_data);
         }
+        Ok(())
     }
 
     /// When there are gaps in the indexing, it is treated as an Object instead.
-    fn handle_object(data: &mut [Vec<Vec<DataPoint>>], json_data: Value) {
+    fn handle_object(data: &mut [Vec<Vec<DataPoint>>], json_data: Value) -> Result<(), serde_json::Error> {
         // May be missing index
-        for i in 0..7 {
+        for (i, day) in data.iter_mut().enumerate() {
             let new_data = match json_data.get(i.to_string()) {
-                Some(day_data) => Self::get_vec_from_day(day_data),
+                Some(day_data) => Self::get_vec_from_day(day_data)?,
                 None => Vec::new(),
             };
-            data[i].push(new_data);
+            day.push(new_data);
         }
+        Ok(())
     }
 
-    fn get_vec_from_day(day_data: &Value) -> Vec<DataPoint> {
-        let mut data: Vec<DataPoint> = Vec::new();
+    fn get_vec_from_day(day_data: &Value) -> Result<Vec<DataPoint>, serde_json::Error> {
         if day_data.is_null() {
-            println!("Unexpected Error - day_data is null - get_vec_from_day");
-            return Vec::new();
+            return Ok(Vec::new());
         }
-        for (key, val) in day_data.as_object().unwrap() {
-            data.push(DataPoint {
-                time: key.parse().unwrap(),
-                occupancy: val.as_u64().unwrap() as u16,
-            });
+        let day_data: HashMap<String, u16> = serde_json::from_value(day_data.clone())?;
+        let mut data: Vec<DataPoint> = Vec::with_capacity(day_data.len());
+        for (key, occupancy) in day_data {
+            let time = key.parse().map_err(|_| {
+                serde::de::Error::custom(format!("Invalid time key: {}", key))
+            })?;
+            data.push(DataPoint { time, occupancy });
         }
-        data
+        Ok(data)
     }
 
     pub fn get_data(&self) -> &[Vec<Vec<DataPoint>>; 7] {
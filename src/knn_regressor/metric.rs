@@ -0,0 +1,23 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+/// What the KNN regressor learns from and predicts for a facility.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// The percentage the page shows.
+    #[default]
+    Occupancy,
+    /// The number of people, which needs a `headcount` selector.
+    Headcount,
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Occupancy => write!(f, "occupancy"),
+            Self::Headcount => write!(f, "headcount"),
+        }
+    }
+}
@@ -1,2 +1,3 @@
 pub mod regressor;
 pub mod data;
+pub mod metric;
@@ -1,5 +1,3 @@
-use std::{f64, usize};
-
 use super::data::{Data, DataPoint};
 
 pub struct Regressor {
@@ -0,0 +1,9 @@
+pub mod config;
+pub mod core_functions;
+pub mod facility;
+pub mod firebase;
+pub mod health;
+pub mod knn_regressor;
+pub mod sleeper;
+pub mod store;
+pub mod web_scraper;
@@ -1,57 +1,436 @@
-mod core_functions;
-mod firebase;
-mod knn_regressor;
-mod sleeper;
-mod web_scraper;
-
-use std::{collections::HashMap, fs};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    env, fs,
+    hash::{Hash, Hasher},
+    path::Path,
+    sync::Arc,
+};
 
-use chrono::{DateTime, Datelike, Duration, NaiveDate, Weekday};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Timelike, Weekday};
 use chrono_tz::Tz;
-use core_functions::{
-    error_logger::error_logger, get_start_of_week, uk_datetime_now, weekday_matcher,
+use gym_backend::{
+    core_functions::{
+        error_logger::error_logger, get_start_of_week, uk_datetime_now, weekday_matcher,
+    },
+    config::Config,
+    facility::Facility,
+    firebase::{credentials::Credentials, firebase::Firebase, listener::Event},
+    health::{
+        monitor::{check_headcount, check_occupancy, Alert, Field, HealthMonitor},
+        notifier::{Notifier, Notifiers},
+    },
+    knn_regressor::{data::{Data, DataPoint}, metric::Metric, regressor::Regressor},
+    sleeper::Sleeper,
+    store::{
+        backend::Store,
+        local_store::LocalStore,
+        outbox::Outbox,
+        retention::Retention,
+    },
+    web_scraper::{
+        archive::PageArchive,
+        extractor::Extractor,
+        fetcher::{Fetcher, Recorder},
+        reextract::reextract,
+        schedule::Schedule,
+        schedule_change::ScheduleChange,
+        timing::Timing,
+    },
 };
-use firebase::firebase::Firebase;
-use knn_regressor::{data::Data, regressor::Regressor};
 
-use serde_json::json;
-use sleeper::Sleeper;
-use web_scraper::{extractor, schedule::Schedule};
+use futures_util::{future::join_all, StreamExt};
+use serde_json::{json, Map, Value};
 
 use tokio::{self, join};
 
+const USAGE: &str = "Usage: gym-backend [reextract <from> [<to>]]   (dates as YYYY-MM-DD)";
+
+enum Command {
+    /// Scrape every facility, forever.
+    Run,
+    /// Regenerate the occupancy between two dates from the page archive.
+    Reextract { from: NaiveDate, to: NaiveDate },
+}
+
+impl Command {
+    fn parse(args: &[String]) -> Option<Self> {
+        let date = |arg: &String| NaiveDate::parse_from_str(arg, "%Y-%m-%d").ok();
+        match args {
+            [] => Some(Self::Run),
+            [command, from] if command == "reextract" => Some(Self::Reextract {
+                from: date(from)?,
+                to: uk_datetime_now::now().date_naive(),
+            }),
+            [command, from, to] if command == "reextract" => Some(Self::Reextract {
+                from: date(from)?,
+                to: date(to)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
-    let mut extractor = extractor::Extractor::new_default();
-    let db_url: String = fs::read_to_string("databaseUrl.secret").unwrap();
-    let mut firebase = Firebase::new("serviceAccountKey.json.secret", db_url);
-    let mut sleeper = Sleeper::new(5 * 60, 5 * 60, None);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = match Command::parse(&args) {
+        Some(command) => command,
+        None => {
+            println!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Some(path) = &config.storage.local_store {
+        // No Firebase credentials needed
+        return start(LocalStore::new(path).await, &config, command).await;
+    }
+    let firebase_config = &config.firebase;
+    let firebase = match &firebase_config.emulator_host {
+        Some(host) => Ok(Firebase::emulator(&format!("http://{}/", host), &firebase_config.namespace)),
+        None => {
+            let db_url = match &firebase_config.database_url {
+                Some(db_url) => db_url.clone(),
+                None => match fs::read_to_string(&firebase_config.database_url_file) {
+                    Ok(db_url) => db_url.trim().to_string(),
+                    Err(err) => {
+                        println!("Could not read {}: {}", firebase_config.database_url_file, err);
+                        std::process::exit(1);
+                    }
+                },
+            };
+            Credentials::load(firebase_config.credentials, &firebase_config.service_account_file)
+                .and_then(|credentials| Firebase::with_credentials(credentials, db_url))
+        }
+    };
+    match firebase {
+        Ok(firebase) => {
+            let firebase = Arc::new(firebase);
+            firebase.spawn_token_refresher();
+            if let Command::Run = command {
+                for facility in &config.scraper.facilities {
+                    tokio::spawn(watch_for_edits(firebase.clone(), facility.clone()));
+                }
+            }
+            start(firebase, &config, command).await
+        }
+        Err(err) => {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Streams the facility's data and drops its cached KNN data whenever an
+/// earlier week or a published schedule is edited (e.g. a manual correction
+/// by an admin), so the next round regenerates the predictions.
+async fn watch_for_edits(firebase: Arc<Firebase>, facility: Facility) {
+    let location = facility.data_location();
+    let location = location.as_str();
+    // A hash of each key rather than a copy of the whole history
+    let mut hashes: HashMap<String, Option<u64>> = HashMap::new();
+    let mut connected_before = false;
 
     loop {
-        let scrape_result = extractor.scrape().await;
-        if scrape_result.is_err() {
-            sleeper.async_sleep_error().await;
+        if let Err(err) = firebase.handle_auth_token().await {
+            error_logger(&format!("{} Watcher Error - Auth Token: {}", facility.name, err)).await;
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
             continue;
         }
-        let schedule = extractor.scrape_schedule().await;
-        let occupancy = extractor.scrape_occupancy().await;
+        let events = match firebase.listen(location).await {
+            Ok(events) => events,
+            Err(_) => {
+                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                continue;
+            }
+        };
+        let mut events = Box::pin(events);
+        // Every connection starts with a put of all the data
+        let mut snapshot = true;
+        while let Some(event) = events.next().await {
+            let (path, data, is_patch) = match event {
+                Ok(Event::Put { path, data }) => (path, data, false),
+                Ok(Event::Patch { path, data }) => (path, data, true),
+                Ok(_) => continue,
+                Err(err) => {
+                    error_logger(&format!("{} Watcher Error - {}", facility.name, err)).await;
+                    break;
+                }
+            };
+            let changed = apply_event(&mut hashes, &path, &data, is_patch);
+            // The first snapshot has nothing to compare with. Later ones
+            // catch edits made while we were disconnected.
+            let compare = !snapshot || connected_before;
+            snapshot = false;
+            connected_before = true;
+            if compare && changed.iter().any(|key| affects_predictions(&facility, key)) {
+                println!("Watcher - {} data or schedule edited. Invalidating KNN data.", facility.name);
+                let _ = tokio::fs::remove_file(facility.knn_data_path()).await;
+                let _ = tokio::fs::remove_file(facility.knn_tomorrow_data_path()).await;
+            }
+        }
+        // The stream ends on auth_revoked, cancel or a dropped connection
+    }
+}
+
+/// Number of path segments in a key of the listened location: a week, or
+/// `schedule/<week>` and `headcount/<week>`, which are kept by week too.
+fn key_depth(first: &str) -> usize {
+    match first {
+        "schedule" | "headcount" => 2,
+        _ => 1,
+    }
+}
+
+/// Updates `hashes` for a `put` or `patch` of `data` at `path` and returns
+/// the keys it changed. A key only partly sent changed for sure, as events
+/// are only sent for changes, and its hash becomes unknown (`None`).
+fn apply_event(
+    hashes: &mut HashMap<String, Option<u64>>,
+    path: &str,
+    data: &Value,
+    is_patch: bool,
+) -> Vec<String> {
+    let segments: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    let depth = segments.first().map_or(1, |first| key_depth(first));
+    if segments.len() > depth || (segments.len() == depth && is_patch) {
+        let key = segments[..depth].join("/");
+        hashes.insert(key.clone(), None);
+        return vec![key];
+    }
+    if segments.len() == depth {
+        let key = segments.join("/");
+        let after = (!data.is_null()).then(|| hash(data));
+        let before = match after {
+            Some(_) => hashes.insert(key.clone(), after),
+            None => hashes.remove(&key),
+        };
+        let unchanged = match (before, after) {
+            (None, None) => true,
+            (Some(Some(before)), Some(after)) => before == after,
+            _ => false,
+        };
+        return if unchanged { Vec::new() } else { vec![key] };
+    }
+
+    // Above the keys, so each child is a key or holds some
+    let prefix = segments.join("/");
+    let child_path = |child: &str| if prefix.is_empty() { child.to_string() } else { format!("{}/{}", prefix, child) };
+    let children = data.as_object().cloned().unwrap_or_default();
+    let mut changed = Vec::new();
+    if !is_patch {
+        // A put replaces everything below `path`, so keys it leaves out are deleted
+        let sent: Vec<String> = children.keys().map(|child| child_path(child)).collect();
+        let deleted: Vec<String> = hashes
+            .keys()
+            .filter(|key| prefix.is_empty() || key.starts_with(&format!("{}/", prefix)))
+            .filter(|key| !sent.iter().any(|sent| *key == sent || key.starts_with(&format!("{}/", sent))))
+            .cloned()
+            .collect();
+        for key in deleted {
+            hashes.remove(&key);
+            changed.push(key);
+        }
+    }
+    for (child, value) in &children {
+        changed.extend(apply_event(hashes, &child_path(child), value, false));
+    }
+    changed
+}
 
-        if schedule.is_none() || occupancy.is_none() {
-            sleeper.async_sleep_error().await;
-            continue;
+fn hash(value: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Schedules and the weeks before this one feed the KNN regressor, as do
+/// earlier weeks of the headcount when that is predicted.
+/// Edits to the current week and `latest` are our own writes.
+fn affects_predictions(facility: &Facility, key: &str) -> bool {
+    if key.starts_with("schedule/") {
+        return true;
+    }
+    let key = match (facility.predict, key.strip_prefix("headcount/")) {
+        (Metric::Occupancy, None) => key,
+        (Metric::Headcount, Some(week)) => week,
+        (Metric::Headcount, None) | (Metric::Occupancy, Some(_)) => return false,
+    };
+    let this_week = get_start_of_week::get(uk_datetime_now::now().date_naive());
+    NaiveDate::parse_from_str(key, "%Y-%m-%d").is_ok_and(|week| week < this_week)
+}
+
+async fn start<S: Store>(store: S, config: &Config, command: Command) {
+    match command {
+        Command::Run => run(store, config).await,
+        Command::Reextract { from, to } => reextract_all(&store, config, from, to).await,
+    }
+}
+
+fn page_archive(config: &Config, facility: &Facility) -> PageArchive {
+    PageArchive::new(Path::new(&config.storage.page_archive_dir).join(&facility.name))
+}
+
+async fn reextract_all<S: Store>(store: &S, config: &Config, from: NaiveDate, to: NaiveDate) {
+    if let Err(err) = store.prepare().await {
+        println!("Store Error - Prepare: {}", err);
+        std::process::exit(1);
+    }
+    for facility in &config.scraper.facilities {
+        match reextract(store, facility, &page_archive(config, facility), from, to).await {
+            Ok(result) => {
+                for (fetched_at, err) in &result.failures {
+                    println!("{} {} - {}", facility.name, fetched_at, err);
+                }
+                println!(
+                    "{} - {} samples re-extracted, {} pages outside opening hours, {} pages unreadable",
+                    facility.name,
+                    result.samples,
+                    result.outside_hours,
+                    result.failures.len()
+                );
+            }
+            Err(err) => println!("{} Re-extract Error - {}", facility.name, err),
+        }
+    }
+}
+
+/// Scrapes every facility concurrently, each on its own schedule.
+async fn run<S: Store>(store: S, config: &Config) {
+    let retention = Retention::new(config.retention.weeks, &config.retention.archive_dir);
+    join_all(
+        config
+            .scraper
+            .facilities
+            .iter()
+            .map(|facility| run_facility(&store, facility, config, &retention)),
+    )
+    .await;
+}
+
+async fn run_facility<S: Store>(store: &S, facility: &Facility, config: &Config, retention: &Retention) {
+    // Selectors were checked when the config was loaded
+    let extractor = match Extractor::for_facility(facility, &config.scraper.user_agent) {
+        Ok(extractor) => extractor,
+        Err(err) => {
+            error_logger(&format!("{} Extract Error - {}", facility.name, err)).await;
+            return;
+        }
+    };
+    let archive = config.storage.archive_pages.then(|| page_archive(config, facility));
+    match &config.scraper.record_dir {
+        Some(dir) => {
+            let recorder = Recorder::new(extractor.fetcher().clone(), dir);
+            let extractor = extractor.with_fetcher(recorder);
+            scrape_loop(store, facility, config, retention, archive, extractor).await
+        }
+        None => scrape_loop(store, facility, config, retention, archive, extractor).await,
+    }
+}
+
+async fn scrape_loop<S: Store, F: Fetcher>(
+    store: &S,
+    facility: &Facility,
+    config: &Config,
+    retention: &Retention,
+    archive: Option<PageArchive>,
+    mut extractor: Extractor<F>,
+) {
+    let mut sleeper = Sleeper::new(config.sleeper.frequency, config.sleeper.error_wait, None);
+    let mut monitor = HealthMonitor::new(&facility.name, config.alerts.threshold);
+    let notifiers = Notifiers::from_config(&config.alerts);
+    let mut outbox = Outbox::open(&facility.outbox_path()).await;
+    let mut last_pruned: Option<NaiveDate> = None;
+    // Date-specific hours last skipped, so each is only logged once
+    let mut skipped_exceptions: Vec<String> = Vec::new();
+    let latest_schedule_location = format!("{}/latest/schedule", facility.data_location());
+    // The hours last published, to notice when they change
+    let mut published: Option<Schedule> = match store.get_as(&latest_schedule_location).await {
+        Ok(published) => published,
+        Err(err) => {
+            error_logger(&format!("{} Schedule Error - Load latest: {}", facility.name, err)).await;
+            None
         }
+    };
+    let k = config.regressor.k;
 
-        let schedule = schedule.expect("Unexpected Error");
-        let occupancy = occupancy.expect("Unexpected Error");
+    // Replay anything left over from the last run
+    if !outbox.is_empty() && store.prepare().await.is_ok() {
+        let _ = outbox.flush(store).await;
+    }
 
-        let schedule_data = json!(schedule).to_string();
+    loop {
+        let scrape_result = extractor.scrape().await;
+        if let Some(alert) = monitor.observe(Field::Page, &scrape_result) {
+            raise(&notifiers, &alert);
+        }
+        if scrape_result.is_err() {
+            sleeper.async_sleep_error().await;
+            continue;
+        }
+        // Archived before parsing, so pages we fail to read are kept too
+        if let Some(archive) = &archive {
+            let fetched_at = uk_datetime_now::now().naive_local();
+            for (url, page) in extractor.pages() {
+                if let Err(err) = archive.add(url, page, fetched_at).await {
+                    error_logger(&format!("{} Archive Error - {}", facility.name, err)).await;
+                }
+            }
+        }
+        let schedule = extractor.scrape_schedule();
+        let skipped: Vec<String> = extractor.skipped_exceptions().iter().map(ToString::to_string).collect();
+        for err in skipped.iter().filter(|err| !skipped_exceptions.contains(err)) {
+            error_logger(&format!("{} Schedule Error - Skipped: {}", facility.name, err)).await;
+        }
+        skipped_exceptions = skipped;
+        let occupancy = extractor.scrape_occupancy().and_then(check_occupancy);
+        let headcount = extractor.scrape_headcount().and_then(check_headcount);
+        let alerts = [
+            monitor.observe(Field::Schedule, &schedule),
+            monitor.observe(Field::Occupancy, &occupancy),
+            monitor.observe(Field::Headcount, &headcount),
+        ];
+        for alert in alerts.iter().flatten() {
+            raise(&notifiers, alert);
+        }
+        let (schedule, occupancy) = match (schedule, occupancy) {
+            (Ok(schedule), Ok(occupancy)) => (schedule, occupancy),
+            (Err(err), _) | (_, Err(err)) => {
+                error_logger(&format!("{} Extract Error - {}", facility.name, err)).await;
+                sleeper.async_sleep_error().await;
+                continue;
+            }
+        };
+        // Extra to the percentage, so the sample is kept without it
+        let headcount = match headcount {
+            Ok(headcount) => headcount,
+            Err(err) => {
+                error_logger(&format!("{} Headcount Error - {}", facility.name, err)).await;
+                None
+            }
+        };
+
+        let schedule_data = match serde_json::to_value(&schedule) {
+            Ok(schedule_data) => schedule_data,
+            Err(err) => {
+                error_logger(&format!("{} Schedule Error - Serialize: {}", facility.name, err)).await;
+                sleeper.async_sleep_error().await;
+                continue;
+            }
+        };
         sleeper.set_schedule(schedule);
 
         if !sleeper
             .is_standard_interval()
             .expect("Unexpected Error - Unwrap on Sleeper Schedule")
         {
-            println!("Too early");
+            println!("{} - Too early", facility.name);
             sleeper.sleep().await;
             continue;
         }
@@ -59,71 +438,147 @@ async fn main() {
         let uk_now = uk_datetime_now::now();
         let key = uk_now.format("%H%M").to_string();
         let occupancy_data = prepare_occupancy_json(&key, occupancy);
-        let latest_occupancy_location = "rs_data/data/latest/data";
-        let latest_schedule_location = "rs_data/data/latest/schedule";
-
-        if firebase.handle_auth_token().await.is_err() {
-            error_logger("Firebase Error - Auth Token").await;
-            sleeper.async_sleep_error().await;
-            continue;
-        }
+        let latest_occupancy_location = format!("{}/latest/data", facility.data_location());
 
         let latest_occupancy_data =
             prepare_occupancy_json(&uk_now.format("%Y-%m-%d-%H-%M").to_string(), occupancy);
 
-        let (occupancy_location, schedule_location) = prepare_location(uk_now);
-        let data_insert = firebase.update(occupancy_location, &occupancy_data);
-        let schedule_insert = firebase.set(schedule_location, &schedule_data);
-        let latest_occupancy_set = firebase.set(
-            latest_occupancy_location.to_string(),
-            &latest_occupancy_data,
-        );
-        let latest_schedule_set =
-            firebase.set(latest_schedule_location.to_string(), &schedule_data);
+        let (occupancy_location, schedule_location) = prepare_location(facility, uk_now);
+        // History and latest are written together so they never disagree
+        let mut batch = store
+            .batch()
+            .update(&occupancy_location, occupancy_data)
+            .set(&schedule_location, schedule_data.clone())
+            .set(&latest_occupancy_location, Value::Object(latest_occupancy_data))
+            .set(&latest_schedule_location, schedule_data);
+        if let Some(headcount) = headcount {
+            let latest_key = uk_now.format("%Y-%m-%d-%H-%M").to_string();
+            let counts = [
+                (facility.headcount_location(), "headcount", Some(headcount.people)),
+                (facility.capacity_location(), "capacity", headcount.capacity),
+            ];
+            for (location, name, count) in counts {
+                let Some(count) = count else { continue };
+                batch = batch
+                    .update(&week_day_location(&location, uk_now), prepare_count_json(&key, count))
+                    .set(
+                        &format!("{}/latest/{}", facility.data_location(), name),
+                        Value::Object(prepare_count_json(&latest_key, count)),
+                    );
+            }
+        }
+        let change = published
+            .as_ref()
+            .and_then(|previous| ScheduleChange::detect(previous, sleeper.get_schedule(), uk_now.naive_local()));
+        if let Some(change) = change {
+            println!("{} - Opening hours changed: {} day(s)", facility.name, change.changes.len());
+            match serde_json::to_value(&change) {
+                Ok(data) => {
+                    let location = format!("{}/{}", facility.schedule_changes_location(), change.key());
+                    batch = batch.set(&location, data);
+                }
+                Err(err) => error_logger(&format!("{} Schedule Error - Serialize change: {}", facility.name, err)).await,
+            }
+        }
+        published = Some(sleeper.get_schedule().clone());
+        // Journal the sample before anything can go wrong with the network
+        let write = batch.into_write();
+        let unjournaled = match outbox.enqueue(write.clone()).await {
+            Ok(()) => None,
+            Err(err) => {
+                error_logger(&format!("{} Outbox Error - Enqueue: {}", facility.name, err)).await;
+                // Sent without the outbox rather than not at all
+                Some(write)
+            }
+        };
+
+        if let Err(err) = store.prepare().await {
+            error_logger(&format!("Store Error - Prepare: {}", err)).await;
+            sleeper.async_sleep_error().await;
+            continue;
+        }
 
         // Make these concurrent. join! does not do them in parallel!
-        join!(
+        // Write failures are logged and stay in the outbox for the next round.
+        let _ = join!(
             sleeper.sleep(),
             make_predictions(
-                &firebase,
+                store,
+                facility,
+                k,
                 sleeper.get_schedule(),
                 sleeper.get_frequency() / 60
             ),
-            data_insert,
-            schedule_insert,
-            latest_schedule_set,
-            latest_occupancy_set,
+            outbox.flush(store),
+            async {
+                if let Some(write) = &unjournaled {
+                    if let Err(err) = write.send(store).await {
+                        error_logger(&format!("{} Store Error - Unjournaled write: {}", facility.name, err)).await;
+                    }
+                }
+            },
         );
+
+        // Once a day is plenty
+        let today = uk_datetime_now::now().date_naive();
+        if last_pruned != Some(today) {
+            match retention.prune(store, &facility.prefix, today, k).await {
+                Ok(_) => last_pruned = Some(today),
+                Err(err) => error_logger(&format!("{} Retention Error - {}", facility.name, err)).await,
+            }
+        }
     }
 }
 
-fn prepare_occupancy_json(key: &str, occupancy: u8) -> String {
-    format!("{{ \"{}\":{} }}", key, occupancy)
+// Sent in the background, so a slow notifier does not delay the next scrape
+fn raise(notifiers: &Notifiers, alert: &Alert) {
+    let (notifiers, alert) = (notifiers.clone(), alert.clone());
+    tokio::spawn(async move {
+        if let Err(err) = notifiers.notify(&alert).await {
+            error_logger(&format!("{} Alert Error - {}: {}", alert.facility, alert, err)).await;
+        }
+    });
+}
+
+fn prepare_occupancy_json(key: &str, occupancy: u8) -> Map<String, Value> {
+    let mut data = Map::new();
+    data.insert(key.to_string(), json!(occupancy));
+    data
+}
+
+fn prepare_count_json(key: &str, count: u16) -> Map<String, Value> {
+    let mut data = Map::new();
+    data.insert(key.to_string(), json!(count));
+    data
 }
 
 // Returns (Occupancy Location, Schedule Location)
-fn prepare_location(now: DateTime<Tz>) -> (String, String) {
+fn prepare_location(facility: &Facility, now: DateTime<Tz>) -> (String, String) {
     let start_of_week = get_start_of_week::get(now.date_naive());
     let start_of_week = start_of_week.format("%Y-%m-%d");
-    let weekday_num = weekday_matcher::get_num(now.weekday()).to_string();
-    let occupancy_location = format!("rs_data/data/{}/{}", start_of_week, weekday_num);
-    let schedule_location = format!("rs_data/data/schedule/{}", start_of_week);
+    let occupancy_location = week_day_location(&facility.data_location(), now);
+    let schedule_location = format!("{}/schedule/{}", facility.data_location(), start_of_week);
 
     (occupancy_location, schedule_location)
 }
 
-async fn make_predictions(firebase: &Firebase, schedule: &Schedule, frequency: u64) {
-    // SQRT((15 * 60) / 5) -> Sqrt of number of data points
-    // 8.9
-    let k = 3;
-    let path = "knn_regressor.data";
+/// `<location>/<week>/<weekday>`, where the samples of `now` go.
+fn week_day_location(location: &str, now: DateTime<Tz>) -> String {
+    let start_of_week = get_start_of_week::get(now.date_naive());
+    let weekday_num = weekday_matcher::get_num(now.weekday());
+    format!("{}/{}/{}", location, start_of_week.format("%Y-%m-%d"), weekday_num)
+}
+
+async fn make_predictions<S: Store>(store: &S, facility: &Facility, k: usize, schedule: &Schedule, frequency: u64) {
+    let path = facility.knn_data_path();
+    let path = path.as_str();
 
     let now = uk_datetime_now::now();
     let now_date: NaiveDate = now.date_naive();
     let mut new = false;
 
     if now.weekday() == Weekday::Sun {
-        predict_monday(firebase, k, schedule, frequency, now_date).await;
+        predict_monday(store, facility, k, schedule, frequency, now_date).await;
     }
 
     let data = match Data::from_file(path).await {
@@ -131,14 +586,14 @@ async fn make_predictions(firebase: &Firebase, schedule: &Schedule, frequency: u
             if data.get_for_date() != &get_start_of_week::get(now_date).to_string() {
                 // New Week
                 new = true;
-                Data::new(firebase, k, now_date).await
+                Data::new(store, &facility.history_location(), k, now_date).await
             } else {
-                data
+                Ok(data)
             }
         }
         None => {
             new = true;
-            Data::new(firebase, k, now_date).await
+            Data::new(store, &facility.history_location(), k, now_date).await
         }
     };
 
@@ -146,49 +601,41 @@ async fn make_predictions(firebase: &Firebase, schedule: &Schedule, frequency: u
         return;
     }
 
+    let data = match data {
+        Ok(data) => data,
+        Err(err) => {
+            error_logger(&format!("{} Prediction Error - Fetching data: {}", facility.name, err)).await;
+            return;
+        }
+    };
+
     data.write_to_file(path).await;
 
     let regressor = Regressor::new(data, k);
 
     // Predict for the entire week
+    let start_of_week = get_start_of_week::get(now_date);
     for i in 0..7 {
-        let timings = schedule.get_timings_from_weekday(weekday_matcher::get_weekday(i));
-        let start: u16 = timings
-            .get_opening()
-            .unwrap()
-            .format("%H%M")
-            .to_string()
-            .parse()
-            .unwrap();
-        let end: u16 = timings
-            .get_closing()
-            .unwrap()
-            .format("%H%M")
-            .to_string()
-            .parse()
-            .unwrap();
-
-        let predictions = regressor.predict_range(start, end, frequency as u16, i);
+        let date = start_of_week + Duration::days(i as i64);
+        let predictions = predict_day(&regressor, schedule.timing_on(date), frequency, i);
 
         let location = format!(
-            "rs_data/prediction/{}/{}",
-            get_start_of_week::get(now_date).to_string(),
+            "{}/{}/{}",
+            facility.prediction_location(),
+            start_of_week,
             i
         );
 
-        let mut map: HashMap<String, u16> = HashMap::new();
-
-        for values in predictions {
-            map.insert(values.get_time().to_string(), values.get_occupancy());
+        let map = prediction_map(&predictions);
+        if let Err(err) = store.set_as(&location, &map).await {
+            error_logger(&format!("{} Prediction Error - Upload: {}", facility.name, err)).await;
         }
-
-        let data = serde_json::to_string(&map).unwrap();
-        firebase.set(location, &data).await;
     }
 }
 
-async fn predict_monday(
-    firebase: &Firebase,
+async fn predict_monday<S: Store>(
+    store: &S,
+    facility: &Facility,
     k: usize,
     schedule: &Schedule,
     frequency: u64,
@@ -197,22 +644,24 @@ async fn predict_monday(
     // This is for that +1 Edge case.
     // This is indeed inefficient as it will be overwritten when monday hits.
     // But this is so much simpler than doing Today + Tomorrow prediction (due to edge cases)
-    let date = date + Duration::days(7);
-    let path = "knn_regressor_tomorrow.data";
+    // The coming Monday, so the day fetched, the weekday predicted and its hours agree
+    let date = date + Duration::days(7 - date.weekday().num_days_from_monday() as i64);
+    let path = facility.knn_tomorrow_data_path();
+    let path = path.as_str();
     let mut new = false;
     let data = match Data::from_file(path).await {
         Some(data) => {
             if data.get_for_date() != &get_start_of_week::get(date).to_string() {
                 // New Week
                 new = true;
-                Data::new(firebase, k, date).await
+                Data::new_for_days(store, &facility.history_location(), k, date, 0..=0).await
             } else {
-                data
+                Ok(data)
             }
         }
         None => {
             new = true;
-            Data::new(firebase, k, date).await
+            Data::new_for_days(store, &facility.history_location(), k, date, 0..=0).await
         }
     };
 
@@ -220,42 +669,56 @@ async fn predict_monday(
         return;
     }
 
+    let data = match data {
+        Ok(data) => data,
+        Err(err) => {
+            error_logger(&format!("{} Prediction Error - Fetching data: {}", facility.name, err)).await;
+            return;
+        }
+    };
+
     data.write_to_file(path).await;
 
     let regressor = Regressor::new(data, k);
 
     let weekday = weekday_matcher::get_num(date.weekday());
 
-    let timings = schedule.get_timings_from_weekday(date.weekday());
-    let start: u16 = timings
-        .get_opening()
-        .unwrap()
-        .format("%H%M")
-        .to_string()
-        .parse()
-        .unwrap();
-    let end: u16 = timings
-        .get_closing()
-        .unwrap()
-        .format("%H%M")
-        .to_string()
-        .parse()
-        .unwrap();
-
-    let predictions = regressor.predict_range(start, end, frequency as u16, weekday);
+    let predictions = predict_day(&regressor, schedule.timing_on(date), frequency, weekday);
 
     let location = format!(
-        "rs_data/prediction/{}/{}",
-        get_start_of_week::get(date).to_string(),
+        "{}/{}/{}",
+        facility.prediction_location(),
+        get_start_of_week::get(date),
         0
     );
 
-    let mut map: HashMap<String, u16> = HashMap::new();
-
-    for values in predictions {
-        map.insert(values.get_time().to_string(), values.get_occupancy());
+    let map = prediction_map(&predictions);
+    if let Err(err) = store.set_as(&location, &map).await {
+        error_logger(&format!("{} Prediction Error - Upload: {}", facility.name, err)).await;
     }
+}
+
+/// Predictions for each session of the day. None when closed.
+fn predict_day(regressor: &Regressor, timing: &Timing, frequency: u64, weekday: usize) -> Vec<DataPoint> {
+    let hhmm = |time: NaiveTime| (time.hour() * 100 + time.minute()) as u16;
+    timing
+        .get_sessions()
+        .iter()
+        .flat_map(|session| {
+            // Predictions stop at midnight
+            let closing = match session.closes_after_midnight() {
+                true => 2359,
+                false => hhmm(session.get_closing()),
+            };
+            regressor.predict_range(hhmm(session.get_opening()), closing, frequency as u16, weekday)
+        })
+        .collect()
+}
 
-    let data = serde_json::to_string(&map).unwrap();
-    firebase.set(location, &data).await;
+// HHMM -> Predicted occupancy
+fn prediction_map(predictions: &[DataPoint]) -> HashMap<String, u16> {
+    predictions
+        .iter()
+        .map(|point| (point.get_time().to_string(), point.get_occupancy()))
+        .collect()
 }
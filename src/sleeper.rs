@@ -1,4 +1,4 @@
-use chrono::{Datelike, Duration, NaiveTime, Timelike};
+use chrono::{Duration, NaiveDateTime, NaiveTime, Timelike};
 
 use crate::web_scraper::{schedule::Schedule, timing::Timing};
 use crate::core_functions::{error_logger::error_logger, uk_datetime_now};
@@ -29,69 +29,52 @@ impl Sleeper {
     }
 
     pub async fn sleep(&self) {
-        let schedule = match &self.schedule {
-            Some(schedule) => schedule,
+        let now = uk_datetime_now::now().naive_local();
+        let wake = match self.next_wake(now) {
+            Some(wake) => wake,
             None => {
                 error_logger("No schedule. Error Sleep").await;
                 //TODO: add error sleeper
                 return;
             }
         };
-        let now = uk_datetime_now::now();
-        let now_time = now.time();
-        let weekday = now.weekday();
-        let timing: &Timing = schedule.get_timings_from_weekday(weekday);
-        let day_end = NaiveTime::from_hms_opt(23, 59, 59).unwrap();
-        // Check if open today
-        if !timing.is_open() {
-            // TODO: HANDLE CLOSED 
-            let default_open = self.default.get_opening().unwrap();
-            let diff = day_end - now_time + Duration::minutes(
-                (default_open.hour() * 60 + default_open.minute()) as i64
-            );
-            Self::async_sleep(diff).await;
-        }
+        Self::async_sleep(wake - now).await;
+    }
 
-        let opening_time = timing.get_opening().unwrap();
-        let closing_time = timing.get_closing().unwrap();
+    /// When to take the next sample after `now` (UK time): the next
+    /// `frequency` boundary while open, otherwise the next opening. Honours
+    /// split sessions and date-specific hours. `None` without a schedule.
+    pub fn next_wake(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let schedule = self.schedule.as_ref()?;
+        let today = now.date();
+        let now_time = now.time();
+        let timing: &Timing = schedule.timing_on(today);
 
-        // Check if within opening hours
-        if opening_time < now_time && now_time < closing_time {
-            let now_second_stamp: u64 = (now.minute() * 60 + now_time.second()).into();
+        if schedule.is_open_at(now) {
+            let now_second_stamp: u64 = (now_time.minute() * 60 + now_time.second()).into();
             let diff = self.frequency - (now_second_stamp % self.frequency);
-            Self::async_sleep(Duration::seconds(diff as i64)).await;
-            return;
+            let now = now.with_nanosecond(0).unwrap_or(now);
+            return Some(now + Duration::seconds(diff as i64));
         }
-        else if opening_time > now_time {
-            // Too early
-            let diff = opening_time - now_time;
-            Self::async_sleep(diff).await;
+        // Too early, or between sessions
+        if let Some(opening) = timing.next_opening_after(now_time) {
+            return Some(today.and_time(opening));
         }
-        else if now_time > closing_time {
-            // Too late
-            let tomorrow_opening_time = schedule.get_timings_from_weekday_tomorrow(weekday);
-            if !tomorrow_opening_time.is_open() {
-                //TODO: handle closed days
-                // For now let's sleep to the default timing 
-                let default_open = self.default.get_opening().unwrap();
-                let diff = day_end - now_time + Duration::minutes(
-                    (default_open.hour() * 60 + default_open.minute()) as i64
-                );
-                Self::async_sleep(diff).await;
+        // Too late. Skip closed days
+        for days in 1..=7 {
+            let date = today + Duration::days(days);
+            if let Some(opening) = schedule.timing_on(date).get_opening() {
+                return Some(date.and_time(opening));
             }
-            let tomorrow_opening_time = tomorrow_opening_time.get_opening().unwrap();
-            let diff: Duration = day_end - now_time + Duration::minutes(
-                (tomorrow_opening_time.hour() * 60 + tomorrow_opening_time.minute()) as i64
-            );
-            Self::async_sleep(diff).await;
-            // Schedule CAN change - Especially when it's Sunday. TOOD: take care of it
         }
+        // Closed all week. Look again tomorrow, the schedule may have changed
+        let default_open = self.default.get_opening().unwrap();
+        Some((today + Duration::days(1)).and_time(default_open))
     }
 
     async fn async_sleep(diff: Duration) {
         println!("Sleeping {} seconds", diff.num_seconds());
-        let std_duration = std::time::Duration::new(diff.num_seconds() as u64, 0);
-        tokio::time::sleep(std_duration).await;
+        tokio::time::sleep(diff.to_std().unwrap_or_default()).await;
     }
 
     pub async fn async_sleep_error(&self) {
@@ -101,15 +84,8 @@ impl Sleeper {
 
     pub fn is_standard_interval(&self) -> Option<bool> {
         let now = uk_datetime_now::now();
-        let now_time = now.time();
-        let weekday = now.weekday();
         let schedule = &self.schedule.as_ref()?;
-        let timing: &Timing = schedule.get_timings_from_weekday(weekday);
-
-        let opening_time = timing.get_opening()?;
-        let closing_time = timing.get_closing()?;
-
-        Some(opening_time <= now_time && now_time < closing_time)
+        Some(schedule.is_open_at(now.naive_local()))
     }
 
     pub fn get_frequency(&self) -> u64 {
@@ -117,6 +93,6 @@ impl Sleeper {
     }
 
     pub fn get_schedule(&self) -> &Schedule {
-        &self.schedule.as_ref().unwrap()
+        self.schedule.as_ref().unwrap()
     }
 }
@@ -0,0 +1,117 @@
+use std::{error::Error, future::Future, sync::Arc};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{batch::Batch, query::Query};
+
+/// A hierarchical JSON store addressed by `/` separated locations.
+///
+/// Semantics follow the Firebase Realtime Database REST API so the scraper
+/// loop and the KNN data loader can run against any implementation.
+pub trait Store: Sync {
+    type Error: Error + From<serde_json::Error> + Send;
+
+    /// Whether the request that failed with `err` could succeed if sent again
+    /// later. Writes that can never succeed are set aside by the `Outbox`.
+    fn is_transient(err: &Self::Error) -> bool {
+        let _ = err;
+        true
+    }
+
+    /// Make sure the store is ready for a round of requests (e.g. refresh auth tokens).
+    fn prepare(&self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Returns the JSON at `location`. Missing locations are returned as `null`.
+    fn get(&self, location: &str) -> impl Future<Output = Result<String, Self::Error>> + Send;
+
+    /// Returns the data at `location`, deserialised.
+    fn get_as<T: DeserializeOwned>(&self, location: &str)
+        -> impl Future<Output = Result<T, Self::Error>> + Send {
+        async move {
+            let data = self.get(location).await?;
+            Ok(serde_json::from_str(&data)?)
+        }
+    }
+
+    /// Returns the JSON at `location`, filtered by `query`.
+    fn query(&self, location: &str, query: &Query)
+        -> impl Future<Output = Result<String, Self::Error>> + Send;
+
+    /// Runs `query` and deserialises the result.
+    fn query_as<T: DeserializeOwned>(&self, location: &str, query: &Query)
+        -> impl Future<Output = Result<T, Self::Error>> + Send {
+        async move {
+            let data = self.query(location, query).await?;
+            Ok(serde_json::from_str(&data)?)
+        }
+    }
+
+    /// Replaces the data at `location`.
+    fn set(&self, location: &str, data: &str)
+        -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Merges the children of the JSON object `data` into `location`.
+    fn update(&self, location: &str, data: &str)
+        -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Serialises `value` and replaces the data at `location` with it.
+    fn set_as<T: Serialize + ?Sized>(&self, location: &str, value: &T)
+        -> impl Future<Output = Result<(), Self::Error>> + Send {
+        let data = serde_json::to_string(value);
+        async move { self.set(location, &data?).await }
+    }
+
+    /// Serialises `value`, which must be a map or struct, and merges its
+    /// fields into `location`.
+    fn update_as<T: Serialize + ?Sized>(&self, location: &str, value: &T)
+        -> impl Future<Output = Result<(), Self::Error>> + Send {
+        let data = serde_json::to_string(value);
+        async move { self.update(location, &data?).await }
+    }
+
+    /// Removes `location` and everything under it.
+    fn delete(&self, location: &str) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Starts a set of writes that are committed atomically in one request.
+    fn batch(&self) -> Batch<'_, Self> {
+        Batch::new(self)
+    }
+}
+
+/// Lets one store be shared between tasks.
+impl<S: Store + Send> Store for Arc<S> {
+    type Error = S::Error;
+
+    fn is_transient(err: &Self::Error) -> bool {
+        S::is_transient(err)
+    }
+
+    fn prepare(&self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        S::prepare(self)
+    }
+
+    fn get(&self, location: &str) -> impl Future<Output = Result<String, Self::Error>> + Send {
+        S::get(self, location)
+    }
+
+    fn query(&self, location: &str, query: &Query)
+        -> impl Future<Output = Result<String, Self::Error>> + Send {
+        S::query(self, location, query)
+    }
+
+    fn set(&self, location: &str, data: &str)
+        -> impl Future<Output = Result<(), Self::Error>> + Send {
+        S::set(self, location, data)
+    }
+
+    fn update(&self, location: &str, data: &str)
+        -> impl Future<Output = Result<(), Self::Error>> + Send {
+        S::update(self, location, data)
+    }
+
+    fn delete(&self, location: &str) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        S::delete(self, location)
+    }
+}
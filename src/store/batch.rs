@@ -0,0 +1,90 @@
+use serde_json::{Map, Value};
+
+use super::{backend::Store, outbox::Write};
+
+/// Several writes sent as one multi-location update, so either all of them
+/// land or none do.
+///
+/// Locations must not overlap (one may not be inside another).
+pub struct Batch<'a, S: Store + ?Sized> {
+    store: &'a S,
+    writes: Vec<(String, Value)>,
+}
+
+impl<'a, S: Store + ?Sized> Batch<'a, S> {
+    pub fn new(store: &'a S) -> Self {
+        Self {
+            store,
+            writes: Vec::new(),
+        }
+    }
+
+    /// Replaces the data at `location`.
+    pub fn set(mut self, location: &str, value: Value) -> Self {
+        self.writes.push((location.trim_matches('/').to_string(), value));
+        self
+    }
+
+    /// Merges `children` into `location`, leaving its other children alone.
+    pub fn update(mut self, location: &str, children: Map<String, Value>) -> Self {
+        let location = location.trim_matches('/');
+        for (key, child) in children {
+            self.writes.push((format!("{}/{}", location, key), child));
+        }
+        self
+    }
+
+    pub fn delete(self, location: &str) -> Self {
+        self.set(location, Value::Null)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// The single update equivalent to the batch, rooted at the deepest
+    /// location common to every write.
+    pub fn into_write(self) -> Write {
+        let root = common_root(self.writes.iter().map(|(location, _)| location.as_str()));
+        let children: Map<String, Value> = self
+            .writes
+            .into_iter()
+            .map(|(location, value)| {
+                let relative = location[root.len()..].trim_start_matches('/').to_string();
+                (relative, value)
+            })
+            .collect();
+        Write::Update {
+            location: root,
+            data: Value::Object(children).to_string(),
+        }
+    }
+
+    pub async fn commit(self) -> Result<(), S::Error> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let store = self.store;
+        self.into_write().send(store).await
+    }
+}
+
+/// Longest shared prefix of whole path segments. Never a full location,
+/// since every write needs a key relative to the root.
+fn common_root<'a>(locations: impl Iterator<Item = &'a str>) -> String {
+    let mut root: Option<Vec<&str>> = None;
+    for location in locations {
+        let mut segments: Vec<&str> = location.split('/').collect();
+        segments.pop();
+        root = Some(match root {
+            None => segments,
+            Some(root) => root
+                .into_iter()
+                .zip(segments)
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+    root.unwrap_or_default().join("/")
+}
@@ -0,0 +1,101 @@
+use std::{error::Error, fmt, io};
+
+use serde_json::{from_str, Map, Value};
+use tokio::{fs, sync::Mutex};
+
+use crate::core_functions::error_logger::error_logger;
+
+use super::{backend::Store, query::Query, tree};
+
+/// A `Store` kept in a single JSON file on disk.
+/// Useful for running without Google credentials (e.g. staging).
+pub struct LocalStore {
+    path: String,
+    root: Mutex<Value>,
+}
+
+#[derive(Debug)]
+pub enum LocalStoreError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for LocalStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "Local store IO error: {}", err),
+            Self::Json(err) => write!(f, "Local store JSON error: {}", err),
+        }
+    }
+}
+
+impl Error for LocalStoreError {}
+
+impl From<serde_json::Error> for LocalStoreError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl LocalStore {
+    pub async fn new(path: &str) -> Self {
+        let mut root = Value::Object(Map::new());
+        if let Ok(contents) = fs::read_to_string(path).await {
+            // Set through `tree` so arrays in a hand-written file become index-keyed objects
+            match from_str(&contents) {
+                Ok(contents) => tree::set(&mut root, "", contents),
+                Err(_) => println!("Could not parse {}. Starting with an empty store.", path),
+            }
+        }
+        Self {
+            path: path.to_string(),
+            root: Mutex::new(root),
+        }
+    }
+
+    async fn persist(&self, root: &Value) -> Result<(), LocalStoreError> {
+        if let Err(err) = fs::write(&self.path, root.to_string()).await {
+            error_logger("Local Store Error - Write to disk").await;
+            return Err(LocalStoreError::Io(err));
+        }
+        Ok(())
+    }
+}
+
+impl Store for LocalStore {
+    type Error = LocalStoreError;
+
+    async fn get(&self, location: &str) -> Result<String, LocalStoreError> {
+        let root = self.root.lock().await;
+        // Arrays come back as arrays, as they do from Firebase
+        Ok(tree::coerce_arrays(tree::get(&root, location).unwrap_or(&Value::Null)).to_string())
+    }
+
+    async fn query(&self, location: &str, query: &Query) -> Result<String, LocalStoreError> {
+        let root = self.root.lock().await;
+        Ok(tree::coerce_arrays(&query.apply(tree::get(&root, location).unwrap_or(&Value::Null))).to_string())
+    }
+
+    async fn set(&self, location: &str, data: &str) -> Result<(), LocalStoreError> {
+        let value: Value = from_str(data)?;
+        let mut root = self.root.lock().await;
+        tree::set(&mut root, location, value);
+        println!("Local set {}", location);
+        self.persist(&root).await
+    }
+
+    async fn update(&self, location: &str, data: &str) -> Result<(), LocalStoreError> {
+        let children: Map<String, Value> = from_str(data)?;
+        let mut root = self.root.lock().await;
+        tree::update(&mut root, location, children);
+        println!("Local update {}", location);
+        self.persist(&root).await
+    }
+
+    async fn delete(&self, location: &str) -> Result<(), LocalStoreError> {
+        let mut root = self.root.lock().await;
+        tree::delete(&mut root, location);
+        println!("Local delete {}", location);
+        self.persist(&root).await
+    }
+}
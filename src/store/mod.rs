@@ -0,0 +1,7 @@
+pub mod backend;
+pub mod batch;
+pub mod local_store;
+pub mod outbox;
+pub mod query;
+pub mod retention;
+pub mod tree;
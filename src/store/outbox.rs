@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::{self, AsyncWriteExt},
+};
+
+use crate::core_functions::error_logger::error_logger;
+
+use super::backend::Store;
+
+/// A write waiting to be sent to a `Store`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Write {
+    Set { location: String, data: String },
+    Update { location: String, data: String },
+}
+
+impl Write {
+    pub async fn send<S: Store + ?Sized>(&self, store: &S) -> Result<(), S::Error> {
+        match self {
+            Self::Set { location, data } => store.set(location, data).await,
+            Self::Update { location, data } => store.update(location, data).await,
+        }
+    }
+}
+
+/// One line of the journal.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum JournalEntry {
+    Pending { id: u64, write: Write },
+    Done { id: u64 },
+}
+
+/// Durable queue of writes backed by an append-only journal on disk.
+///
+/// Every write is journalled before it is sent and marked done after the
+/// store accepts it, so nothing is lost to network outages or restarts.
+/// Writes are always sent in the order they were queued. A write the store
+/// rejects for good (e.g. a 400) is moved to `<path>.dead` instead of
+/// holding up the writes behind it.
+pub struct Outbox {
+    path: String,
+    pending: VecDeque<(u64, Write)>,
+    next_id: u64,
+    /// An append failed part way, so the journal may not end with a newline.
+    torn: bool,
+}
+
+impl Outbox {
+    /// Opens the journal at `path`, recovering any writes that were never sent.
+    pub async fn open(path: &str) -> Self {
+        let mut pending: VecDeque<(u64, Write)> = VecDeque::new();
+        let mut next_id = 0;
+        let mut contents = fs::read_to_string(path).await.unwrap_or_default();
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            // A crash mid-append leaves a partial last line. It was never
+            // reported as queued, and later appends would be glued onto it.
+            let end = contents.rfind('\n').map_or(0, |index| index + 1);
+            contents.truncate(end);
+            let truncated = match OpenOptions::new().write(true).open(path).await {
+                Ok(file) => file.set_len(end as u64).await,
+                Err(err) => Err(err),
+            };
+            match truncated {
+                Ok(()) => error_logger("Outbox Error - Dropped a partial journal line").await,
+                Err(err) => error_logger(&format!("Outbox Error - Truncate partial journal line: {}", err)).await,
+            }
+        }
+        for line in contents.lines().filter(|line| !line.is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(JournalEntry::Pending { id, write }) => {
+                    next_id = next_id.max(id + 1);
+                    pending.push_back((id, write));
+                }
+                Ok(JournalEntry::Done { id }) => pending.retain(|(pending_id, _)| *pending_id != id),
+                // Left by an append that failed part way, and reported as failed
+                Err(_) => error_logger("Outbox Error - Skipping corrupt journal line").await,
+            }
+        }
+        if !pending.is_empty() {
+            println!("Outbox recovered {} pending writes", pending.len());
+        }
+        Self {
+            path: path.to_string(),
+            pending,
+            next_id,
+            torn: false,
+        }
+    }
+
+    /// Writes the store rejected for good, one JSON write per line.
+    pub fn dead_letter_path(&self) -> String {
+        format!("{}.dead", self.path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Durably records `write`. It is sent on the next `flush`.
+    pub async fn enqueue(&mut self, write: Write) -> io::Result<()> {
+        let id = self.next_id;
+        self.append(&JournalEntry::Pending {
+            id,
+            write: write.clone(),
+        })
+        .await?;
+        self.next_id += 1;
+        self.pending.push_back((id, write));
+        Ok(())
+    }
+
+    /// Sends pending writes in order, stopping at the first transient failure
+    /// so that later writes never overtake earlier ones. Writes that fail for
+    /// good are dead-lettered and skipped. Returns the number sent.
+    pub async fn flush<S: Store>(&mut self, store: &S) -> Result<usize, S::Error> {
+        let mut sent = 0;
+        while let Some((id, write)) = self.pending.front().cloned() {
+            match write.send(store).await {
+                Ok(()) => sent += 1,
+                Err(err) if S::is_transient(&err) => {
+                    error_logger(&format!("Outbox Error - {} writes pending: {}", self.len(), err)).await;
+                    return Err(err);
+                }
+                Err(err) => {
+                    error_logger(&format!("Outbox Error - Dead-lettering write {}: {}", id, err)).await;
+                    if let Err(dead_err) = self.dead_letter(&write).await {
+                        // Kept in the queue rather than lost
+                        error_logger(&format!("Outbox Error - Dead letter: {}", dead_err)).await;
+                        return Err(err);
+                    }
+                }
+            }
+            self.pending.pop_front();
+            if let Err(err) = self.append(&JournalEntry::Done { id }).await {
+                error_logger(&format!("Outbox Error - Journal: {}", err)).await;
+            }
+        }
+        self.compact().await;
+        Ok(sent)
+    }
+
+    async fn append(&mut self, entry: &JournalEntry) -> io::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        if self.torn {
+            // Ends the partial line so this entry is read on its own
+            line.insert(0, '\n');
+        }
+        let result = Self::append_line(&self.path, &line).await;
+        self.torn = result.is_err();
+        result
+    }
+
+    async fn dead_letter(&self, write: &Write) -> io::Result<()> {
+        let mut line = serde_json::to_string(write)?;
+        line.push('\n');
+        Self::append_line(&self.dead_letter_path(), &line).await
+    }
+
+    async fn append_line(path: &str, line: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.sync_data().await
+    }
+
+    /// Once everything has been sent the journal carries no information.
+    async fn compact(&self) {
+        if !self.pending.is_empty() {
+            return;
+        }
+        if let Err(err) = fs::write(&self.path, "").await {
+            error_logger(&format!("Outbox Error - Compact: {}", err)).await;
+        }
+    }
+}
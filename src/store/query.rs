@@ -0,0 +1,263 @@
+use std::cmp::Ordering;
+
+use serde_json::{Map, Value};
+
+use super::tree;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderBy {
+    Key,
+    Value,
+    /// Path of a child of each item, e.g. `"stats/occupancy"`.
+    Child(String),
+}
+
+/// Filters for a read, mirroring the Realtime Database REST query parameters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    order_by: Option<OrderBy>,
+    start_at: Option<Value>,
+    end_at: Option<Value>,
+    equal_to: Option<Value>,
+    limit_to_first: Option<usize>,
+    limit_to_last: Option<usize>,
+    shallow: bool,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn order_by_key(mut self) -> Self {
+        self.order_by = Some(OrderBy::Key);
+        self
+    }
+
+    pub fn order_by_value(mut self) -> Self {
+        self.order_by = Some(OrderBy::Value);
+        self
+    }
+
+    pub fn order_by_child(mut self, child: &str) -> Self {
+        self.order_by = Some(OrderBy::Child(child.to_string()));
+        self
+    }
+
+    pub fn start_at(mut self, value: impl Into<Value>) -> Self {
+        self.start_at = Some(value.into());
+        self
+    }
+
+    pub fn end_at(mut self, value: impl Into<Value>) -> Self {
+        self.end_at = Some(value.into());
+        self
+    }
+
+    pub fn equal_to(mut self, value: impl Into<Value>) -> Self {
+        self.equal_to = Some(value.into());
+        self
+    }
+
+    pub fn limit_to_first(mut self, limit: usize) -> Self {
+        self.limit_to_first = Some(limit);
+        self
+    }
+
+    pub fn limit_to_last(mut self, limit: usize) -> Self {
+        self.limit_to_last = Some(limit);
+        self
+    }
+
+    /// Only return the keys of the children (each mapped to `true`).
+    /// Firebase does not allow this to be combined with the other parameters.
+    pub fn shallow(mut self) -> Self {
+        self.shallow = true;
+        self
+    }
+
+    /// Parses REST query parameters, the inverse of `to_params`.
+    /// Parameters that are not part of a query (`print`, `ns`...) are ignored.
+    pub fn from_params<'a>(
+        params: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self, String> {
+        let mut query = Self::new();
+        for (name, value) in params {
+            let json = || {
+                serde_json::from_str::<Value>(value)
+                    .map_err(|_| format!("{} must be valid JSON: {}", name, value))
+            };
+            let limit = || {
+                value
+                    .parse::<usize>()
+                    .map_err(|_| format!("{} must be a positive integer: {}", name, value))
+            };
+            query = match name {
+                "orderBy" => match json()? {
+                    Value::String(order_by) => match order_by.as_str() {
+                        "$key" => query.order_by_key(),
+                        "$value" => query.order_by_value(),
+                        child => query.order_by_child(child),
+                    },
+                    _ => return Err(format!("orderBy must be a string: {}", value)),
+                },
+                "startAt" => query.start_at(json()?),
+                "endAt" => query.end_at(json()?),
+                "equalTo" => query.equal_to(json()?),
+                "limitToFirst" => query.limit_to_first(limit()?),
+                "limitToLast" => query.limit_to_last(limit()?),
+                "shallow" if value == "true" => query.shallow(),
+                _ => query,
+            };
+        }
+        Ok(query)
+    }
+
+    fn has_filters(&self) -> bool {
+        self.start_at.is_some()
+            || self.end_at.is_some()
+            || self.equal_to.is_some()
+            || self.limit_to_first.is_some()
+            || self.limit_to_last.is_some()
+    }
+
+    /// REST query parameters. Values are JSON encoded as Firebase expects.
+    pub fn to_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if self.shallow {
+            params.push(("shallow", "true".to_string()));
+            return params;
+        }
+        // Filters are rejected by Firebase without an orderBy
+        let order_by = match (&self.order_by, self.has_filters()) {
+            (Some(order_by), _) => Some(order_by.clone()),
+            (None, true) => Some(OrderBy::Key),
+            (None, false) => None,
+        };
+        if let Some(order_by) = order_by {
+            let order_by = match order_by {
+                OrderBy::Key => "$key".to_string(),
+                OrderBy::Value => "$value".to_string(),
+                OrderBy::Child(child) => child,
+            };
+            params.push(("orderBy", Value::String(order_by).to_string()));
+        }
+        if let Some(value) = &self.start_at {
+            params.push(("startAt", value.to_string()));
+        }
+        if let Some(value) = &self.end_at {
+            params.push(("endAt", value.to_string()));
+        }
+        if let Some(value) = &self.equal_to {
+            params.push(("equalTo", value.to_string()));
+        }
+        if let Some(limit) = self.limit_to_first {
+            params.push(("limitToFirst", limit.to_string()));
+        }
+        if let Some(limit) = self.limit_to_last {
+            params.push(("limitToLast", limit.to_string()));
+        }
+        params
+    }
+
+    /// Evaluates the query against `node` the way the Realtime Database does,
+    /// for stores that hold their data locally.
+    pub fn apply(&self, node: &Value) -> Value {
+        let children = match node {
+            Value::Object(children) => children,
+            node => return node.clone(),
+        };
+        if self.shallow {
+            return Value::Object(
+                children
+                    .iter()
+                    .map(|(key, child)| {
+                        let child = if child.is_object() { Value::Bool(true) } else { child.clone() };
+                        (key.clone(), child)
+                    })
+                    .collect(),
+            );
+        }
+        let order_by = match (&self.order_by, self.has_filters()) {
+            (Some(order_by), _) => order_by.clone(),
+            (None, true) => OrderBy::Key,
+            (None, false) => return node.clone(),
+        };
+
+        let sort_value = |key: &String, child: &Value| -> Value {
+            match &order_by {
+                OrderBy::Key => Value::String(key.clone()),
+                OrderBy::Value => child.clone(),
+                OrderBy::Child(path) => tree::get(child, path).cloned().unwrap_or(Value::Null),
+            }
+        };
+        let compare = |a: &Value, b: &Value| -> Ordering {
+            match order_by {
+                OrderBy::Key => compare_keys(a.as_str().unwrap_or(""), b.as_str().unwrap_or("")),
+                _ => compare_values(a, b),
+            }
+        };
+
+        let mut items: Vec<(Value, &String, &Value)> = children
+            .iter()
+            .map(|(key, child)| (sort_value(key, child), key, child))
+            .collect();
+        items.sort_by(|(a, a_key, _), (b, b_key, _)| {
+            compare(a, b).then_with(|| compare_keys(a_key, b_key))
+        });
+        items.retain(|(value, _, _)| {
+            self.start_at.as_ref().is_none_or(|start| compare(value, start).is_ge())
+                && self.end_at.as_ref().is_none_or(|end| compare(value, end).is_le())
+                && self.equal_to.as_ref().is_none_or(|equal| compare(value, equal).is_eq())
+        });
+        if let Some(limit) = self.limit_to_first {
+            items.truncate(limit);
+        }
+        if let Some(limit) = self.limit_to_last {
+            items.drain(..items.len().saturating_sub(limit));
+        }
+
+        let result: Map<String, Value> = items
+            .into_iter()
+            .map(|(_, key, child)| (key.clone(), child.clone()))
+            .collect();
+        Value::Object(result)
+    }
+}
+
+/// Keys that are canonical integers come first, in numeric order.
+/// Times such as `"0630"` are strings as far as Firebase is concerned.
+fn compare_keys(a: &str, b: &str) -> Ordering {
+    match (integer_key(a), integer_key(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+fn integer_key(key: &str) -> Option<i64> {
+    key.parse::<i64>().ok().filter(|number| number.to_string() == key)
+}
+
+/// null < false < true < numbers < strings < objects
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(false) => 1,
+            Value::Bool(true) => 2,
+            Value::Number(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) | Value::Object(_) => 5,
+        }
+    }
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
@@ -0,0 +1,143 @@
+//! Moves weeks that are no longer needed out of the store and into
+//! compressed files on disk, so `rs_data` does not grow forever.
+
+use std::{collections::BTreeSet, error::Error, fmt, io, io::Write as _, path::PathBuf};
+
+use chrono::{Duration, NaiveDate};
+use flate2::{write::GzEncoder, Compression};
+use serde_json::{Map, Value};
+use tokio::fs;
+
+use crate::core_functions::get_start_of_week;
+
+use super::{backend::Store, query::Query};
+
+/// Locations, relative to a facility's prefix, whose children are keyed by
+/// the Monday of their week.
+const WEEK_LOCATIONS: [&str; 6] = [
+    "data",
+    "data/schedule",
+    "data/headcount",
+    "data/capacity",
+    "prediction",
+    "prediction/headcount",
+];
+
+#[derive(Debug)]
+pub enum RetentionError<E> {
+    Store(E),
+    /// The archive could not be written. Nothing was deleted.
+    Archive(io::Error),
+}
+
+impl<E: fmt::Display> fmt::Display for RetentionError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(err) => write!(f, "Store error: {}", err),
+            Self::Archive(err) => write!(f, "Could not write archive: {}", err),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for RetentionError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Store(err) => Some(err),
+            Self::Archive(err) => Some(err),
+        }
+    }
+}
+
+/// Keeps `horizon_weeks` weeks before the current one in the store.
+/// Older weeks are written to `<archive_dir>/<prefix>/<week>.json.gz` and then deleted.
+#[derive(Debug, Clone)]
+pub struct Retention {
+    horizon_weeks: usize,
+    archive_dir: PathBuf,
+}
+
+impl Retention {
+    pub fn new(horizon_weeks: usize, archive_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            horizon_weeks,
+            archive_dir: archive_dir.into(),
+        }
+    }
+
+    /// The oldest week kept. Never fewer than the `k` weeks the KNN
+    /// regressor looks back over, whatever the horizon.
+    pub fn cutoff(&self, today: NaiveDate, k: usize) -> NaiveDate {
+        let weeks = self.horizon_weeks.max(k) as i64;
+        get_start_of_week::get(today) - Duration::weeks(weeks)
+    }
+
+    /// Archives and deletes every week under `prefix` older than the cutoff.
+    /// Returns the weeks that were removed, oldest first.
+    pub async fn prune<S: Store>(
+        &self,
+        store: &S,
+        prefix: &str,
+        today: NaiveDate,
+        k: usize,
+    ) -> Result<Vec<NaiveDate>, RetentionError<S::Error>> {
+        let cutoff = self.cutoff(today, k);
+        let mut weeks = BTreeSet::new();
+        for location in WEEK_LOCATIONS {
+            let keys: Value = store
+                .query_as(&format!("{}/{}", prefix, location), &Query::new().shallow())
+                .await
+                .map_err(RetentionError::Store)?;
+            if let Value::Object(keys) = keys {
+                // Skips `latest`, `schedule`, `headcount`, ...
+                weeks.extend(
+                    keys.keys()
+                        .filter_map(|key| NaiveDate::parse_from_str(key, "%Y-%m-%d").ok())
+                        .filter(|week| *week < cutoff),
+                );
+            }
+        }
+
+        for week in &weeks {
+            let mut archive = Map::new();
+            for location in WEEK_LOCATIONS {
+                let data: Value = store
+                    .get_as(&format!("{}/{}/{}", prefix, location, week))
+                    .await
+                    .map_err(RetentionError::Store)?;
+                archive.insert(location.to_string(), data);
+            }
+            // Only delete once the archive is safely on disk
+            self.write_archive(prefix, *week, &Value::Object(archive))
+                .await
+                .map_err(RetentionError::Archive)?;
+
+            let mut batch = store.batch();
+            for location in WEEK_LOCATIONS {
+                batch = batch.delete(&format!("{}/{}/{}", prefix, location, week));
+            }
+            batch.commit().await.map_err(RetentionError::Store)?;
+            println!("Archived {}/{}", prefix, week);
+        }
+        Ok(weeks.into_iter().collect())
+    }
+
+    pub fn archive_path(&self, prefix: &str, week: NaiveDate) -> PathBuf {
+        self.archive_dir.join(prefix).join(format!("{}.json.gz", week))
+    }
+
+    async fn write_archive(&self, prefix: &str, week: NaiveDate, data: &Value) -> io::Result<()> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data.to_string().as_bytes())?;
+        let compressed = encoder.finish()?;
+
+        // Written aside and renamed so a crash never leaves half an archive
+        let path = self.archive_path(prefix, week);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        let partial = path.with_extension("gz.partial");
+        fs::write(&partial, compressed).await?;
+        fs::File::open(&partial).await?.sync_all().await?;
+        fs::rename(&partial, &path).await
+    }
+}
@@ -0,0 +1,117 @@
+//! In-memory operations on a JSON tree with Realtime Database semantics:
+//! arrays are stored as objects keyed by index, and `null` or empty objects
+//! are never stored.
+
+use serde_json::{Map, Value};
+
+fn segments(location: &str) -> Vec<&str> {
+    location.split('/').filter(|key| !key.is_empty()).collect()
+}
+
+pub fn get<'a>(root: &'a Value, location: &str) -> Option<&'a Value> {
+    segments(location)
+        .into_iter()
+        .try_fold(root, |node, key| node.as_object()?.get(key))
+}
+
+pub fn set(root: &mut Value, location: &str, value: Value) {
+    set_at(root, &segments(location), normalise(value));
+    if root.is_null() {
+        *root = Value::Object(Map::new());
+    }
+}
+
+/// Each key of `children` may itself be a path, as in a multi-location update.
+pub fn update(root: &mut Value, location: &str, children: Map<String, Value>) {
+    for (key, child) in children {
+        set(root, &format!("{}/{}", location, key), child);
+    }
+}
+
+pub fn delete(root: &mut Value, location: &str) {
+    set(root, location, Value::Null);
+}
+
+fn set_at(node: &mut Value, keys: &[&str], value: Value) {
+    let (key, rest) = match keys.split_first() {
+        Some(split) => split,
+        None => {
+            *node = value;
+            return;
+        }
+    };
+    if !node.is_object() {
+        *node = Value::Object(Map::new());
+    }
+    let children = node.as_object_mut().expect("Node was just made an object");
+    let child = children.entry(key.to_string()).or_insert(Value::Null);
+    set_at(child, rest, value);
+    if is_empty(child) {
+        children.remove(*key);
+    }
+    if children.is_empty() {
+        *node = Value::Null;
+    }
+}
+
+fn normalise(value: Value) -> Value {
+    match value {
+        Value::Array(items) => normalise(Value::Object(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| (index.to_string(), item))
+                .collect(),
+        )),
+        Value::Object(children) => {
+            let children: Map<String, Value> = children
+                .into_iter()
+                .map(|(key, child)| (key, normalise(child)))
+                .filter(|(_, child)| !is_empty(child))
+                .collect();
+            if children.is_empty() {
+                Value::Null
+            } else {
+                Value::Object(children)
+            }
+        }
+        value => value,
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Object(children) => children.is_empty(),
+        _ => false,
+    }
+}
+
+/// Objects whose keys are mostly consecutive integers come back as arrays,
+/// as the Realtime Database does when more than half the indices are present.
+pub fn coerce_arrays(value: &Value) -> Value {
+    let children = match value {
+        Value::Object(children) => children,
+        value => return value.clone(),
+    };
+    let indices: Option<Vec<usize>> = children
+        .keys()
+        .map(|key| key.parse::<usize>().ok().filter(|index| index.to_string() == *key))
+        .collect();
+    if let Some(indices) = indices {
+        let max = indices.iter().copied().max().unwrap_or(0);
+        if !indices.is_empty() && indices.len() * 2 > max + 1 {
+            let mut items = vec![Value::Null; max + 1];
+            for (key, child) in children {
+                items[key.parse::<usize>().expect("Checked above")] = coerce_arrays(child);
+            }
+            return Value::Array(items);
+        }
+    }
+    Value::Object(
+        children
+            .iter()
+            .map(|(key, child)| (key.clone(), coerce_arrays(child)))
+            .collect(),
+    )
+}
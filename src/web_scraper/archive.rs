@@ -0,0 +1,122 @@
+//! Every scraped page, kept on disk so a sample can be audited and the
+//! history re-extracted after a parser fix.
+//!
+//! Pages are partitioned by (UK) date and stored once per day per content
+//! hash: `<dir>/<date>/<sha256>.html.gz`. `<dir>/<date>/index` lists when
+//! each page was fetched, one `<HH:MM:SS>\t<sha256>\t<url>` line per fetch.
+
+use std::{
+    io::{self, Read as _, Write as _},
+    path::PathBuf,
+};
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncWriteExt};
+
+const INDEX: &str = "index";
+const TIME_FORMAT: &str = "%H:%M:%S";
+
+/// One fetch listed in the index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchivedPage {
+    pub fetched_at: NaiveDateTime,
+    pub url: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PageArchive {
+    dir: PathBuf,
+}
+
+impl PageArchive {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Stores `page`, fetched from `url` at `fetched_at` (UK time).
+    /// Returns false if the same page was already stored that day.
+    pub async fn add(&self, url: &str, page: &str, fetched_at: NaiveDateTime) -> io::Result<bool> {
+        let hash = format!("{:x}", Sha256::digest(page.as_bytes()));
+        let dir = self.dir.join(fetched_at.date().to_string());
+        fs::create_dir_all(&dir).await?;
+
+        let path = dir.join(format!("{}.html.gz", hash));
+        let new = fs::metadata(&path).await.is_err();
+        if new {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(page.as_bytes())?;
+            // Renamed into place so the index never points at half a page
+            let partial = path.with_extension("gz.partial");
+            fs::write(&partial, encoder.finish()?).await?;
+            fs::rename(&partial, &path).await?;
+        }
+
+        let mut index = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(INDEX))
+            .await?;
+        let line = format!("{}\t{}\t{}\n", fetched_at.format(TIME_FORMAT), hash, url);
+        index.write_all(line.as_bytes()).await?;
+        Ok(new)
+    }
+
+    /// Days with archived pages, oldest first.
+    pub async fn dates(&self) -> io::Result<Vec<NaiveDate>> {
+        let mut dates = Vec::new();
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(dates),
+            Err(err) => return Err(err),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(date) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| NaiveDate::parse_from_str(name, "%Y-%m-%d").ok())
+            {
+                dates.push(date);
+            }
+        }
+        dates.sort();
+        Ok(dates)
+    }
+
+    /// Fetches on `date`, in the order they happened.
+    pub async fn pages(&self, date: NaiveDate) -> io::Result<Vec<ArchivedPage>> {
+        let index = match fs::read_to_string(self.dir.join(date.to_string()).join(INDEX)).await {
+            Ok(index) => index,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        // A line cut short by a crash is skipped
+        Ok(index
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let time = NaiveTime::parse_from_str(fields.next()?, TIME_FORMAT).ok()?;
+                let hash = fields.next()?.to_string();
+                let url = fields.next()?.to_string();
+                Some(ArchivedPage {
+                    fetched_at: date.and_time(time),
+                    url,
+                    hash,
+                })
+            })
+            .collect())
+    }
+
+    pub async fn read(&self, page: &ArchivedPage) -> io::Result<String> {
+        let path = self
+            .dir
+            .join(page.fetched_at.date().to_string())
+            .join(format!("{}.html.gz", page.hash));
+        let compressed = fs::read(path).await?;
+        let mut html = String::new();
+        GzDecoder::new(compressed.as_slice()).read_to_string(&mut html)?;
+        Ok(html)
+    }
+}
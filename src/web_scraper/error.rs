@@ -0,0 +1,83 @@
+use std::{error::Error, fmt, io};
+
+use chrono::NaiveDateTime;
+
+use super::time_parser::TimeError;
+
+#[derive(Debug)]
+pub enum ExtractError {
+    /// `scrape` has not succeeded yet.
+    NotScraped,
+    /// The gym website could not be reached.
+    Network(reqwest::Error),
+    /// A saved page could not be read.
+    Page { path: String, err: io::Error },
+    /// Nothing was recorded for `url` at or before `at`.
+    NoRecording { url: String, at: NaiveDateTime },
+    /// A configured CSS selector does not parse.
+    InvalidSelector { selector: String, reason: String },
+    /// Nothing on the page matches the selector, so the layout has probably changed.
+    MissingElement { selector: String },
+    /// No element matched by `selector` contains `label`.
+    MissingLabel { selector: String, label: String },
+    /// The text after the occupancy label is not a percentage.
+    InvalidOccupancy(String),
+    /// The headcount or capacity element holds no number.
+    InvalidHeadcount(String),
+    /// The schedule should list one timing per weekday.
+    ScheduleDays { selector: String, found: usize },
+    /// A schedule entry is neither `CLOSED` nor `<opening> to <closing>`.
+    InvalidTiming(String),
+    /// A schedule entry has a time we cannot read.
+    InvalidTime { entry: String, err: TimeError },
+    /// Read, but implausible, e.g. an occupancy of 140%.
+    Anomaly(String),
+    /// A date-specific entry is not `<date>: <hours>`.
+    InvalidException(String),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotScraped => write!(f, "Nothing scraped yet"),
+            Self::Network(err) => write!(f, "Network error: {}", err),
+            Self::Page { path, err } => write!(f, "Could not read page {}: {}", path, err),
+            Self::NoRecording { url, at } => write!(f, "No recording of {} at or before {}", url, at),
+            Self::InvalidSelector { selector, reason } => {
+                write!(f, "Invalid selector `{}`: {}", selector, reason)
+            }
+            Self::MissingElement { selector } => {
+                write!(f, "No element matches `{}`, has the page layout changed?", selector)
+            }
+            Self::MissingLabel { selector, label } => {
+                write!(f, "No element matching `{}` contains {:?}", selector, label)
+            }
+            Self::InvalidOccupancy(text) => write!(f, "Could not read an occupancy from {:?}", text),
+            Self::InvalidHeadcount(text) => write!(f, "Could not read a headcount from {:?}", text),
+            Self::ScheduleDays { selector, found } => {
+                write!(f, "Expected 7 schedule entries matching `{}`, found {}", selector, found)
+            }
+            Self::InvalidTiming(text) => write!(f, "Could not read opening hours from {:?}", text),
+            Self::InvalidTime { entry, err } => write!(f, "Could not read opening hours from {:?}: {}", entry, err),
+            Self::Anomaly(text) => write!(f, "Implausible {}", text),
+            Self::InvalidException(text) => write!(f, "Could not read date-specific hours from {:?}", text),
+        }
+    }
+}
+
+impl Error for ExtractError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Network(err) => Some(err),
+            Self::Page { err, .. } => Some(err),
+            Self::InvalidTime { err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ExtractError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Network(err)
+    }
+}
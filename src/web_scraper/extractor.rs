@@ -1,121 +1,253 @@
-use std::fs;
+use chrono::NaiveDate;
+use scraper::{ElementRef, Html, Selector};
 
-use regex::Regex;
+use crate::{core_functions::error_logger::error_logger, facility::Facility};
 
-use reqwest::{Client, RequestBuilder, Method};
+use super::{
+    error::ExtractError,
+    fetcher::{Fetcher, HttpFetcher},
+    headcount::{parse_number, Headcount},
+    schedule::Schedule,
+    selectors::{CompiledSelectors, Selectors},
+};
 
-use crate::core_functions::error_logger::error_logger;
-
-use super::schedule::Schedule;
-
-pub struct Extractor {
-    client: Client,
+pub struct Extractor<F = HttpFetcher> {
+    fetcher: F,
     url: String,
-    user_agent: String,
-    occupancy_regex: Regex,
-    schedule_regex: Regex,
-    scrape_result: Option<String>
-    // request: RequestBuilder
+    /// Page listing the opening hours, when they are not on `url`.
+    schedule_url: Option<String>,
+    selectors: Selectors,
+    compiled: CompiledSelectors,
+    scrape_result: Option<String>,
+    schedule_result: Option<String>,
+    /// Week the pages were fetched in, when not this one.
+    week_start: Option<NaiveDate>,
 }
 
 impl Extractor {
-    pub fn new_default() -> Self {
-        let config_data = Self::parse_config(&"config.cfg".to_string());
-        Self {
-            client: Client::new(),
-            url: config_data[0].to_string(),
-            user_agent: config_data[1].to_string(),
-            occupancy_regex: Regex::new(r"Occupancy:\s+(\d+)%").unwrap(),
-            schedule_regex: Regex::new("<dd class=\"paired-values-list__value\">(.*?)</dd>").unwrap(),
+    pub fn for_facility(facility: &Facility, user_agent: &str) -> Result<Self, ExtractError> {
+        let extractor = Self::with_selectors(facility.url.clone(), user_agent.to_string(), facility.selectors.clone())?;
+        Ok(match &facility.schedule_url {
+            Some(schedule_url) => extractor.with_schedule_url(schedule_url.clone()),
+            None => extractor,
+        })
+    }
+
+    pub fn with_selectors(url: String, user_agent: String, selectors: Selectors) -> Result<Self, ExtractError> {
+        Ok(Self {
+            fetcher: HttpFetcher::new(user_agent),
+            url,
+            schedule_url: None,
+            compiled: selectors.compile()?,
+            selectors,
             scrape_result: None,
-        }
+            schedule_result: None,
+            week_start: None,
+        })
     }
 
-    pub fn new(config_path: String) -> Self {
-        let config_data = Self::parse_config(&config_path);
-        Self {
-            client: Client::new(),
-            url: config_data[0].to_string(),
-            user_agent: config_data[1].to_string(),
-            occupancy_regex: Regex::new(r"Occupancy:\s+(\d+)%").unwrap(),
-            schedule_regex: Regex::new("<dd class=\"paired-values-list__value\">(.*?)</dd>").unwrap(),
-            scrape_result: None
-        }
+    /// Parses a saved page instead of fetching one, e.g. for tests.
+    pub fn from_html(html: &str) -> Self {
+        Self::from_html_with_selectors(html, Selectors::default()).expect("Default selectors are valid")
     }
 
-    fn parse_config(src: &String) -> Vec<String> {
-        let mut config_data: Vec<String> = Vec::with_capacity(2);
-        let data = match fs::read_to_string(src) {
-            Ok(data) => data,
-            Err(_) => {
-                println!("Error opening file: {}", src);
-                std::process::exit(1);
-            }
-        };
-        for line in data.lines() {
-            config_data.push(line.to_string());
+    pub fn from_html_with_selectors(html: &str, selectors: Selectors) -> Result<Self, ExtractError> {
+        let mut extractor = Self::with_selectors(String::new(), String::new(), selectors)?;
+        extractor.scrape_result = Some(html.to_string());
+        Ok(extractor)
+    }
+
+    /// Reads the schedule from a second saved page, as `with_schedule_url` does when scraping.
+    pub fn with_schedule_html(mut self, html: &str) -> Self {
+        self.schedule_url = Some(String::new());
+        self.schedule_result = Some(html.to_string());
+        self
+    }
+}
+
+impl<F: Fetcher> Extractor<F> {
+    /// Gets pages from `fetcher` instead, e.g. a `FileFetcher` or a `Recorder`.
+    pub fn with_fetcher<G: Fetcher>(self, fetcher: G) -> Extractor<G> {
+        Extractor {
+            fetcher,
+            url: self.url,
+            schedule_url: self.schedule_url,
+            selectors: self.selectors,
+            compiled: self.compiled,
+            scrape_result: None,
+            schedule_result: None,
+            week_start: self.week_start,
         }
-        config_data
     }
 
-    fn get_request(&self) -> RequestBuilder {
-        self.client.request(Method::GET, &self.url)
-        .header("User-Agent", &self.user_agent)
+    /// Reads the schedule from `schedule_url` instead of the occupancy page.
+    pub fn with_schedule_url(mut self, schedule_url: String) -> Self {
+        self.schedule_url = Some(schedule_url);
+        self
     }
 
-    
-    pub async fn scrape(&mut self) -> Result<(),()> {
-        let response = match self.get_request().send().await {
-            Ok(data) => data,
-            Err(_) => {
-                error_logger("Network Error").await;
-                self.scrape_result = None;
-                return Err(());
-            }
-        };
-        let text = match response.text().await {
-            Ok(text) => text,
-            Err(_) => {
-                error_logger("Failed to get response text.").await;
-                self.scrape_result = None;
-                return Err(());
-            }
-        };
+    /// Dates the schedule to the week of `week_start` rather than this week,
+    /// e.g. for archived pages, so dates without a year resolve as they did then.
+    pub fn with_week_start(mut self, week_start: NaiveDate) -> Self {
+        self.week_start = Some(week_start);
+        self
+    }
+
+    pub fn fetcher(&self) -> &F {
+        &self.fetcher
+    }
+
+    pub fn fetcher_mut(&mut self) -> &mut F {
+        &mut self.fetcher
+    }
+
+    /// The pages fetched by the last `scrape`, with their URLs.
+    pub fn pages(&self) -> Vec<(&str, &str)> {
+        let schedule = self.schedule_url.as_deref().zip(self.schedule_result.as_deref());
+        self.scrape_result
+            .as_deref()
+            .map(|page| (self.url.as_str(), page))
+            .into_iter()
+            .chain(schedule)
+            .collect()
+    }
+
+    async fn fetch(&self, url: &str) -> Result<String, ExtractError> {
+        let text = self.fetcher.fetch(url).await;
+        if let Err(err) = &text {
+            error_logger(&format!("Scrape Error - {}", err)).await;
+        }
+        text
+    }
+
+    pub async fn scrape(&mut self) -> Result<(), ExtractError> {
+        self.scrape_result = None;
+        self.schedule_result = None;
+        let text = self.fetch(&self.url).await?;
+        if let Some(schedule_url) = &self.schedule_url {
+            self.schedule_result = Some(self.fetch(schedule_url).await?);
+        }
         self.scrape_result = Some(text);
         Ok(())
     }
 
-    pub async fn scrape_occupancy(&self) -> Option<u8> {
-        let text = &self.scrape_result.clone()?;
-        let regex_match = match self.occupancy_regex.captures(&text) {
-            Some(data) => data,
-            None => {
-                error_logger("Occupancy Scrape Error. Regex Fail").await;
-                return None;
+    fn page(&self) -> Result<Html, ExtractError> {
+        let text = self.scrape_result.as_ref().ok_or(ExtractError::NotScraped)?;
+        Ok(Html::parse_document(text))
+    }
+
+    fn schedule_page(&self) -> Result<Html, ExtractError> {
+        match (&self.schedule_url, &self.schedule_result) {
+            (None, _) => self.page(),
+            (Some(_), Some(text)) => Ok(Html::parse_document(text)),
+            (Some(_), None) => Err(ExtractError::NotScraped),
+        }
+    }
+
+    pub fn scrape_occupancy(&self) -> Result<u8, ExtractError> {
+        let page = self.page()?;
+        let label = &self.selectors.occupancy_label;
+        let text = |element: &ElementRef| element.text().collect::<String>();
+
+        let mut found = false;
+        // The innermost element holding the label, so surrounding text is ignored
+        let holder = page
+            .select(&self.compiled.occupancy)
+            .inspect(|_| found = true)
+            .map(|element| text(&element))
+            .filter(|text| text.contains(label.as_str()))
+            .min_by_key(|text| text.len());
+        let holder = match holder {
+            Some(holder) => holder,
+            None if found => {
+                return Err(ExtractError::MissingLabel {
+                    selector: self.selectors.occupancy.clone(),
+                    label: label.clone(),
+                })
             }
-        };
-        let result: &str = regex_match.get(1).map_or("0", |m| m.as_str());
-        let result: u8 = match result.parse() {
-            Ok(num) => num,
-            Err(_) => {
-                error_logger("Occupancy Scrape Error. Parse to u8 fail.").await;
-                return None;
+            None => {
+                return Err(ExtractError::MissingElement {
+                    selector: self.selectors.occupancy.clone(),
+                })
             }
         };
-        Some(result)
+
+        let after_label = holder.split_once(label.as_str()).map_or("", |(_, rest)| rest).trim_start();
+        let percentage = after_label
+            .split_once('%')
+            .map(|(number, _)| number.trim())
+            .ok_or_else(|| ExtractError::InvalidOccupancy(after_label.to_string()))?;
+        percentage
+            .parse()
+            .map_err(|_| ExtractError::InvalidOccupancy(after_label.to_string()))
     }
 
-    pub async fn scrape_schedule(&self) -> Option<Schedule> {
-        let text = &self.scrape_result.clone()?;
-        let schedules = self.schedule_regex.captures_iter(text);
-        let schedule = match Schedule::form_schedule_timings(schedules) {
-            Some(data) => data,
-            None => {
-                error_logger("Scrape Schedule Error").await;
-                return None;
-            }
+    /// `None` unless the facility has a `headcount` selector.
+    pub fn scrape_headcount(&self) -> Result<Option<Headcount>, ExtractError> {
+        let (Some(selector), Some(compiled)) = (&self.selectors.headcount, &self.compiled.headcount) else {
+            return Ok(None);
         };
-        Some(schedule)
+        let page = self.page()?;
+        let text = Self::first_text(&page, selector, compiled)?;
+        let mut headcount = Headcount::parse(&text).ok_or(ExtractError::InvalidHeadcount(text))?;
+
+        if let (Some(selector), Some(compiled)) = (&self.selectors.capacity, &self.compiled.capacity) {
+            let text = Self::first_text(&page, selector, compiled)?;
+            headcount.capacity = Some(parse_number(&text).ok_or(ExtractError::InvalidHeadcount(text))?);
+        }
+        Ok(Some(headcount))
+    }
+
+    fn first_text(page: &Html, selector: &str, compiled: &Selector) -> Result<String, ExtractError> {
+        page.select(compiled)
+            .next()
+            .map(|element| element.text().collect::<String>().trim().to_string())
+            .ok_or_else(|| ExtractError::MissingElement {
+                selector: selector.to_string(),
+            })
+    }
+
+    /// The week's hours. Date-specific hours that cannot be read are left
+    /// out, see `skipped_exceptions`.
+    pub fn scrape_schedule(&self) -> Result<Schedule, ExtractError> {
+        self.parse_schedule().map(|(schedule, _)| schedule)
+    }
+
+    /// Date-specific hours `scrape_schedule` could not read, to be logged.
+    pub fn skipped_exceptions(&self) -> Vec<ExtractError> {
+        self.parse_schedule().map(|(_, skipped)| skipped).unwrap_or_default()
+    }
+
+    fn parse_schedule(&self) -> Result<(Schedule, Vec<ExtractError>), ExtractError> {
+        let page = self.schedule_page()?;
+        let entries: Vec<String> = page
+            .select(&self.compiled.schedule)
+            .map(|element| element.text().collect::<String>())
+            .collect();
+        if entries.is_empty() {
+            return Err(ExtractError::MissingElement {
+                selector: self.selectors.schedule.clone(),
+            });
+        }
+        if entries.len() != 7 {
+            return Err(ExtractError::ScheduleDays {
+                selector: self.selectors.schedule.clone(),
+                found: entries.len(),
+            });
+        }
+        let mut schedule = Schedule::from_entries(entries.iter().map(|entry| entry.as_str()))?;
+        if let Some(week_start) = self.week_start {
+            schedule = schedule.with_week_start(week_start);
+        }
+        if let Some(exceptions) = &self.compiled.exceptions {
+            let exceptions: Vec<String> = page
+                .select(exceptions)
+                .map(|element| element.text().collect::<String>())
+                .collect();
+            let skipped = schedule.add_exceptions(exceptions.iter().map(|entry| entry.as_str()));
+            return Ok((schedule, skipped));
+        }
+        Ok((schedule, Vec::new()))
     }
 
 }
@@ -0,0 +1,184 @@
+//! Where the scraper gets its pages from: the website, saved files, or a
+//! recording of earlier scrapes.
+
+use std::{future::Future, path::PathBuf, time::Duration};
+
+use chrono::NaiveDateTime;
+use reqwest::{Client, Method};
+use tokio::fs;
+
+use crate::core_functions::{error_logger::error_logger, uk_datetime_now};
+
+use super::error::ExtractError;
+
+/// Longest a page may take to load, so a hung connection is reported as a
+/// failed scrape instead of stopping the scrape loop.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// UK time a recording was fetched at, used as its file name.
+pub const RECORDING_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+
+pub trait Fetcher {
+    /// The body of the page at `url`.
+    fn fetch(&self, url: &str) -> impl Future<Output = Result<String, ExtractError>> + Send;
+}
+
+/// A file name for the page at `url`, e.g. `sport_wp_st_andrews_ac_uk`.
+pub fn page_name(url: &str) -> String {
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let name: String = url
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    name.trim_matches('_').to_string()
+}
+
+/// Fetches pages from the website.
+#[derive(Debug, Clone)]
+pub struct HttpFetcher {
+    client: Client,
+    user_agent: String,
+}
+
+impl HttpFetcher {
+    pub fn new(user_agent: String) -> Self {
+        Self {
+            client: Self::client(TIMEOUT),
+            user_agent,
+        }
+    }
+
+    /// Gives up on a page after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = Self::client(timeout);
+        self
+    }
+
+    fn client(timeout: Duration) -> Client {
+        Client::builder().timeout(timeout).build().expect("HTTP client to build")
+    }
+}
+
+impl Fetcher for HttpFetcher {
+    async fn fetch(&self, url: &str) -> Result<String, ExtractError> {
+        let response = self
+            .client
+            .request(Method::GET, url)
+            .header("User-Agent", &self.user_agent)
+            .send()
+            .await?;
+        Ok(response.text().await?)
+    }
+}
+
+/// Reads `<dir>/<page_name(url)>.html` instead of going online.
+#[derive(Debug, Clone)]
+pub struct FileFetcher {
+    dir: PathBuf,
+}
+
+impl FileFetcher {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{}.html", page_name(url)))
+    }
+}
+
+impl Fetcher for FileFetcher {
+    async fn fetch(&self, url: &str) -> Result<String, ExtractError> {
+        let path = self.path(url);
+        fs::read_to_string(&path).await.map_err(|err| ExtractError::Page {
+            path: path.to_string_lossy().to_string(),
+            err,
+        })
+    }
+}
+
+/// Saves every page `inner` fetches to `<dir>/<page_name(url)>/<time>.html`,
+/// so a strange sample can be traced back to the page it came from.
+#[derive(Debug, Clone)]
+pub struct Recorder<F> {
+    inner: F,
+    dir: PathBuf,
+}
+
+impl<F: Fetcher> Recorder<F> {
+    pub fn new(inner: F, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            dir: dir.into(),
+        }
+    }
+}
+
+impl<F: Fetcher + Sync> Fetcher for Recorder<F> {
+    async fn fetch(&self, url: &str) -> Result<String, ExtractError> {
+        let page = self.inner.fetch(url).await?;
+        let dir = self.dir.join(page_name(url));
+        let path = dir.join(format!("{}.html", uk_datetime_now::now().format(RECORDING_FORMAT)));
+        // A failed recording is not worth losing the sample over
+        let recorded = match fs::create_dir_all(&dir).await {
+            Ok(()) => fs::write(&path, &page).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = recorded {
+            error_logger(&format!("Recorder Error - {}: {}", path.to_string_lossy(), err)).await;
+        }
+        Ok(page)
+    }
+}
+
+/// Replays what a `Recorder` saved: the latest recording of each page
+/// fetched at or before `at` (UK time).
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    dir: PathBuf,
+    at: NaiveDateTime,
+}
+
+impl Replayer {
+    pub fn new(dir: impl Into<PathBuf>, at: NaiveDateTime) -> Self {
+        Self { dir: dir.into(), at }
+    }
+
+    /// Moves the replay to another point in time.
+    pub fn set_time(&mut self, at: NaiveDateTime) {
+        self.at = at;
+    }
+
+    async fn recording(&self, url: &str) -> Result<PathBuf, ExtractError> {
+        let dir = self.dir.join(page_name(url));
+        let no_recording = || ExtractError::NoRecording {
+            url: url.to_string(),
+            at: self.at,
+        };
+        let mut entries = fs::read_dir(&dir).await.map_err(|_| no_recording())?;
+        let mut latest: Option<(NaiveDateTime, PathBuf)> = None;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let recorded = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| NaiveDateTime::parse_from_str(stem, RECORDING_FORMAT).ok());
+            if let Some(recorded) = recorded.filter(|recorded| *recorded <= self.at) {
+                if latest.as_ref().is_none_or(|(time, _)| recorded > *time) {
+                    latest = Some((recorded, path));
+                }
+            }
+        }
+        latest.map(|(_, path)| path).ok_or_else(no_recording)
+    }
+}
+
+impl Fetcher for Replayer {
+    async fn fetch(&self, url: &str) -> Result<String, ExtractError> {
+        let path = self.recording(url).await?;
+        fs::read_to_string(&path).await.map_err(|err| ExtractError::Page {
+            path: path.to_string_lossy().to_string(),
+            err,
+        })
+    }
+}
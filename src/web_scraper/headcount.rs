@@ -0,0 +1,49 @@
+//! Absolute counts some occupancy pages show next to, or instead of, the
+//! percentage: `37 of 120 people`, `37/120`, `37 people in the gym`,
+//! `Capacity: 120`, ...
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Serialize;
+
+/// `<people> of|out of|/ <capacity>`.
+static OF_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\d[\d,]*)\s*(?:/|of|out of)\s*(\d[\d,]*)").unwrap());
+
+static NUMBER_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d[\d,]*").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Headcount {
+    /// People in the facility.
+    pub people: u16,
+    /// How many people it holds, when the page says.
+    pub capacity: Option<u16>,
+}
+
+impl Headcount {
+    /// Reads `37 of 120 people` or a lone `37`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.to_lowercase();
+        if let Some(captures) = OF_REGEX.captures(&text) {
+            return Some(Self {
+                people: number(&captures[1])?,
+                capacity: Some(number(&captures[2])?),
+            });
+        }
+        Some(Self {
+            people: parse_number(&text)?,
+            capacity: None,
+        })
+    }
+}
+
+/// The first number in `text`, e.g. the `120` of `Capacity: 120`.
+pub fn parse_number(text: &str) -> Option<u16> {
+    number(NUMBER_REGEX.find(text)?.as_str())
+}
+
+/// `1,200` -> 1200
+fn number(text: &str) -> Option<u16> {
+    text.replace(',', "").parse().ok()
+}
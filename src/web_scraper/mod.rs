@@ -1,4 +1,11 @@
+pub mod archive;
+pub mod error;
 pub mod extractor;
+pub mod fetcher;
+pub mod headcount;
+pub mod reextract;
 pub mod schedule;
+pub mod schedule_change;
+pub mod selectors;
+pub mod time_parser;
 pub mod timing;
-
@@ -0,0 +1,164 @@
+//! Regenerates a facility's occupancy history from its `PageArchive`, e.g.
+//! after fixing the parser.
+
+use std::{error::Error, fmt, io};
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use serde_json::{Map, Value};
+
+use crate::{
+    core_functions::{get_start_of_week, weekday_matcher},
+    facility::Facility,
+    health::monitor::{check_headcount, check_occupancy},
+    store::backend::Store,
+};
+
+use super::{archive::PageArchive, error::ExtractError, extractor::Extractor};
+
+#[derive(Debug)]
+pub enum ReextractError<E> {
+    Store(E),
+    Archive(io::Error),
+}
+
+impl<E: fmt::Display> fmt::Display for ReextractError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(err) => write!(f, "Store error: {}", err),
+            Self::Archive(err) => write!(f, "Could not read archive: {}", err),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for ReextractError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Store(err) => Some(err),
+            Self::Archive(err) => Some(err),
+        }
+    }
+}
+
+/// What a re-extraction did.
+#[derive(Debug, Default)]
+pub struct Reextraction {
+    /// Samples written to the store.
+    pub samples: usize,
+    /// Pages fetched outside opening hours, which the scraper does not record.
+    pub outside_hours: usize,
+    /// Pages, or headcounts or date-specific hours on them, that still cannot be read.
+    pub failures: Vec<(NaiveDateTime, ExtractError)>,
+}
+
+/// Re-reads every archived page of `facility` fetched between `from` and
+/// `to` (inclusive) and overwrites the samples they produced, exactly as
+/// the scraper would have recorded them.
+pub async fn reextract<S: Store>(
+    store: &S,
+    facility: &Facility,
+    archive: &PageArchive,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Reextraction, ReextractError<S::Error>> {
+    let mut result = Reextraction::default();
+    let dates = archive.dates().await.map_err(ReextractError::Archive)?;
+    for date in dates.into_iter().filter(|date| from <= *date && *date <= to) {
+        let mut samples = Map::new();
+        let mut headcounts = Map::new();
+        let mut capacities = Map::new();
+        let pages = archive.pages(date).await.map_err(ReextractError::Archive)?;
+        // When the hours are on a page of their own. Unchanged pages are only
+        // archived once a day, so a fetch used the latest one archived by then.
+        let mut schedule_pages: Vec<(NaiveDateTime, String)> = Vec::new();
+        for page in pages.iter().filter(|page| facility.schedule_url.as_ref() == Some(&page.url)) {
+            let html = archive.read(page).await.map_err(ReextractError::Archive)?;
+            schedule_pages.push((page.fetched_at, html));
+        }
+
+        for page in pages.iter().filter(|page| page.url == facility.url) {
+            let html = archive.read(page).await.map_err(ReextractError::Archive)?;
+            let extractor = match Extractor::from_html_with_selectors(&html, facility.selectors.clone()) {
+                Ok(extractor) => extractor.with_week_start(get_start_of_week::get(date)),
+                Err(err) => {
+                    result.failures.push((page.fetched_at, err));
+                    continue;
+                }
+            };
+            let schedule_page = schedule_pages
+                .iter()
+                .rev()
+                .find(|(fetched_at, _)| *fetched_at <= page.fetched_at)
+                // Archived just after, in the same fetch
+                .or(schedule_pages.first())
+                .map(|(_, html)| html);
+            let extractor = match (&facility.schedule_url, schedule_page) {
+                (None, _) => extractor,
+                (Some(_), Some(schedule_page)) => extractor.with_schedule_html(schedule_page),
+                (Some(_), None) => {
+                    result.failures.push((page.fetched_at, ExtractError::NotScraped));
+                    continue;
+                }
+            };
+            let (schedule, occupancy) = match (extractor.scrape_schedule(), extractor.scrape_occupancy().and_then(check_occupancy)) {
+                (Ok(schedule), Ok(occupancy)) => (schedule, occupancy),
+                (Err(err), _) | (_, Err(err)) => {
+                    result.failures.push((page.fetched_at, err));
+                    continue;
+                }
+            };
+            for err in extractor.skipped_exceptions() {
+                result.failures.push((page.fetched_at, err));
+            }
+
+            // Optional, as when scraping
+            let headcount = match extractor.scrape_headcount().and_then(check_headcount) {
+                Ok(headcount) => headcount,
+                Err(err) => {
+                    result.failures.push((page.fetched_at, err));
+                    None
+                }
+            };
+
+            let time = page.fetched_at.time();
+            if schedule.is_open_at(page.fetched_at) {
+                let key = time.format("%H%M").to_string();
+                samples.insert(key.clone(), Value::from(occupancy));
+                if let Some(headcount) = headcount {
+                    headcounts.insert(key.clone(), Value::from(headcount.people));
+                    if let Some(capacity) = headcount.capacity {
+                        capacities.insert(key, Value::from(capacity));
+                    }
+                }
+            } else {
+                result.outside_hours += 1;
+            }
+        }
+
+        if samples.is_empty() {
+            continue;
+        }
+        result.samples += samples.len();
+        let week_day = format!(
+            "{}/{}",
+            get_start_of_week::get(date).format("%Y-%m-%d"),
+            weekday_matcher::get_num(date.weekday())
+        );
+        let series = [
+            (facility.data_location(), samples),
+            (facility.headcount_location(), headcounts),
+            (facility.capacity_location(), capacities),
+        ];
+        for (location, samples) in series {
+            if samples.is_empty() {
+                continue;
+            }
+            let location = format!("{}/{}", location, week_day);
+            store
+                .update_as(&location, &samples)
+                .await
+                .map_err(ReextractError::Store)?;
+            println!("Re-extracted {}", location);
+        }
+    }
+    Ok(result)
+}
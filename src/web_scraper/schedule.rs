@@ -1,77 +1,181 @@
-use chrono::{NaiveTime, NaiveDate, DateTime, Weekday};
-use regex::{Regex, CaptureMatches};
+use std::{collections::BTreeMap, sync::LazyLock};
 
-use crate::core_functions::{get_start_of_week, uk_datetime_now, weekday_matcher};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+use regex::Regex;
 
-use super::timing::Timing;
-use serde::Serialize;
+use crate::{
+    core_functions::{get_start_of_week, uk_datetime_now, weekday_matcher},
+    store::backend::Store,
+};
 
-#[derive(Debug, Serialize)]
+use super::{error::ExtractError, time_parser::parse_time, timing::{Interval, Timing}};
+use serde::{Deserialize, Serialize};
+
+/// A week's published opening hours, as stored at `<prefix>/data/schedule/<week>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Schedule {
-    // #[serde(with ="naive_date_serialize")]
-    #[serde(skip_serializing)]
+    /// Schedules stored before this was kept take it from their location, see `load`.
+    #[serde(default = "current_week_start")]
     week_start: NaiveDate,
     timings: Vec<Timing>,
-    #[serde(skip_serializing)]
-    schedule_regex: Regex,
-    #[serde(skip_serializing)]
-    timing_regex: Regex,
+    /// Hours that differ from the usual week on a given date, e.g. bank holidays.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    overrides: BTreeMap<NaiveDate, Timing>,
+}
+
+fn current_week_start() -> NaiveDate {
+    get_start_of_week::get(uk_datetime_now::now().date_naive())
 }
 
+/// `<opening> to <closing>`, also written with a dash.
+static RANGE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(.+?)\s*(?:\sto\s|–|—|-)\s*(.+)$").unwrap());
+
+/// Separates the sessions of a split day, e.g. `7 am to 1 pm, 4 pm to 10 pm`.
+static SESSION_SEPARATOR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s*(?:,|;|&|\band\b)\s*").unwrap());
+
+/// `<date>: <hours>`, e.g. `Monday 25 December: CLOSED`.
+static EXCEPTION_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(.+?):\s+(.+)$").unwrap());
+
+static ORDINAL_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+)(?:st|nd|rd|th)\b").unwrap());
+
 impl Schedule {
     pub fn empty() -> Self {
         Self {
-            week_start: get_start_of_week::get(uk_datetime_now::now().date_naive()),
+            week_start: current_week_start(),
             timings: Vec::new(),
-            schedule_regex: Regex::new(r"(.*)\sto\s(.*)|CLOSED").unwrap(),
-            timing_regex: Regex::new(r"(\d+).(\d+)(.*)").unwrap()
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    /// The schedule published for `week` under `data_location`, if any.
+    pub async fn load<S: Store>(store: &S, data_location: &str, week: NaiveDate) -> Result<Option<Self>, S::Error> {
+        let schedule: Option<Self> = store
+            .get_as(&format!("{}/schedule/{}", data_location, week.format("%Y-%m-%d")))
+            .await?;
+        Ok(schedule.map(|schedule| schedule.with_week_start(week)))
+    }
+
+    pub fn with_week_start(mut self, week_start: NaiveDate) -> Self {
+        self.week_start = week_start;
+        self
+    }
+
+    /// Builds the week from one entry per weekday, Monday first. Each entry
+    /// is `CLOSED` or `<opening> to <closing>`, e.g. `6:30 am to 10:30 pm`
+    /// or `6am to 1am` for closing after midnight.
+    /// Split days list their sessions separated by `,`, `;`, `&` or `and`.
+    pub fn from_entries<'a>(entries: impl IntoIterator<Item = &'a str>) -> Result<Self, ExtractError> {
+        let mut schedule = Self::empty();
+        for entry in entries {
+            let timing = Self::parse_timing(entry)?;
+            schedule.timings.push(timing);
+        }
+        Ok(schedule)
+    }
+
+    fn parse_timing(entry: &str) -> Result<Timing, ExtractError> {
+        let entry = entry.trim();
+        if entry.eq_ignore_ascii_case("CLOSED") {
+            return Ok(Timing::closed());
+        }
+        let invalid = || ExtractError::InvalidTiming(entry.to_string());
+        let mut sessions = Vec::new();
+        for session in SESSION_SEPARATOR.split(entry) {
+            let timings = RANGE_REGEX.captures(session).ok_or_else(invalid)?;
+            let time = |input: &str| {
+                parse_time(input).map_err(|err| ExtractError::InvalidTime {
+                    entry: entry.to_string(),
+                    err,
+                })
+            };
+            sessions.push(Interval::new(time(&timings[1])?, time(&timings[2])?));
         }
+        Ok(Timing::sessions(sessions))
     }
 
-    pub fn form_schedule_timings(mut matches: CaptureMatches) -> Option<Self> {
-        let mut schedule = Self {
-            week_start: get_start_of_week::get(uk_datetime_now::now().date_naive()),
-            timings: Vec::with_capacity(7),
-            schedule_regex: Regex::new(r"(.*)\sto\s(.*)|CLOSED").unwrap(),
-            timing_regex: Regex::new(r"(\d+).(\d+)(.*)").unwrap()
-        };
-
-        // To skip base 
-        // matches.next();
-        while let Some(inner_html) = matches.next() {
-            let timings = schedule.schedule_regex.captures(inner_html.get(1)?.as_str())?;
-            let timings_match: &str = timings.get(1)?.as_str();
-            if timings_match == "CLOSED" {
-                schedule.timings.push(Timing::closed());
-                continue;
+    /// Adds date-specific hours, one `<date>: <hours>` entry each, e.g.
+    /// `Monday 28 August: 10:00 am to 6:00 pm` or `25/12/2023: CLOSED`.
+    /// Dates without a year are taken to be within six months of this week.
+    /// Entries that cannot be read are skipped and returned, so one odd
+    /// notice does not cost the whole schedule.
+    pub fn add_exceptions<'a>(&mut self, entries: impl IntoIterator<Item = &'a str>) -> Vec<ExtractError> {
+        let mut skipped = Vec::new();
+        for entry in entries {
+            match self.parse_exception(entry.trim()) {
+                Ok((date, timing)) => {
+                    self.overrides.insert(date, timing);
+                }
+                Err(err) => skipped.push(err),
             }
-            let opening = timings_match;
-            let closing = timings.get(2)?.as_str();
-            schedule.timings.push(
-                Timing::open(
-                    schedule.get_naive_time_from_str(opening),
-                    schedule.get_naive_time_from_str(closing),
-                )
-            );
         }
-        Some(schedule) 
+        skipped
+    }
+
+    fn parse_exception(&self, entry: &str) -> Result<(NaiveDate, Timing), ExtractError> {
+        let invalid = || ExtractError::InvalidException(entry.to_string());
+        let captures = EXCEPTION_REGEX.captures(entry).ok_or_else(invalid)?;
+        let date = self.parse_date(&captures[1]).ok_or_else(invalid)?;
+        Ok((date, Self::parse_timing(&captures[2])?))
+    }
+
+    fn parse_date(&self, input: &str) -> Option<NaiveDate> {
+        // 25th -> 25
+        let input = ORDINAL_REGEX.replace_all(input.trim(), "$1");
+        let input = input.trim_end_matches(',');
+        const FORMATS: [&str; 4] = ["%A %d %B %Y", "%d %B %Y", "%A %d %b %Y", "%d %b %Y"];
+        for format in ["%Y-%m-%d", "%d/%m/%Y"].iter().chain(FORMATS.iter()) {
+            if let Ok(date) = NaiveDate::parse_from_str(input, format) {
+                return Some(date);
+            }
+        }
+        // No year given, so the nearest one. Only years where a weekday given
+        // falls on that date parse.
+        let year = self.week_start.year();
+        [year - 1, year, year + 1]
+            .into_iter()
+            .filter_map(|year| {
+                FORMATS
+                    .iter()
+                    .find_map(|format| NaiveDate::parse_from_str(&format!("{} {}", input, year), format).ok())
+            })
+            .min_by_key(|date| (*date - self.week_start).num_days().abs())
+    }
+
+    /// Sets the hours for one date, overriding its weekday.
+    pub fn set_override(&mut self, date: NaiveDate, timing: Timing) {
+        self.overrides.insert(date, timing);
+    }
+
+    pub fn get_overrides(&self) -> &BTreeMap<NaiveDate, Timing> {
+        &self.overrides
+    }
+
+    /// Whether the facility is open at `time` (UK), counting sessions from
+    /// the day before that close after midnight.
+    pub fn is_open_at(&self, time: NaiveDateTime) -> bool {
+        let date = time.date();
+        self.timing_on(date).is_open_at(time.time())
+            || self
+                .timing_on(date - Duration::days(1))
+                .get_sessions()
+                .iter()
+                .any(|session| session.contains_after_midnight(time.time()))
+    }
+
+    /// The hours on `date`: its override if there is one, else its weekday's.
+    pub fn timing_on(&self, date: NaiveDate) -> &Timing {
+        self.overrides
+            .get(&date)
+            .unwrap_or_else(|| self.get_timings_from_weekday(date.weekday()))
     }
 
     pub fn get_week_start(&self) -> &NaiveDate {
         &self.week_start
     }
 
-    fn get_naive_time_from_str(&self, input: &str) -> NaiveTime {
-        let regex_match = self.timing_regex.captures(input).unwrap();
-        NaiveTime::parse_from_str(
-            format!(
-                "{}:{} {}",
-                regex_match.get(1).unwrap().as_str().to_string(),
-                regex_match.get(2).unwrap().as_str().to_string(),
-                regex_match.get(3).unwrap().as_str().to_string(),
-            ).as_str(),
-            "%I:%M %p"
-        ).unwrap()
+    /// The usual hours, Monday first.
+    pub fn get_timings(&self) -> &[Timing] {
+        &self.timings
     }
 
     pub fn get_timings_from_weekday(&self, weekday: Weekday) -> &Timing {
@@ -85,17 +189,3 @@ impl Schedule {
     }
 }
 
-
-impl PartialEq for Schedule{
-    fn eq(&self, other: &Self) -> bool {
-        self.week_start == other.week_start && self.timings == other.timings
-    }
-}
-
-// mod naive_date_serialize {
-//     use chrono::NaiveDate;
-//     use serde::{self, Serializer};
-//     pub fn serialize<S>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-//         serializer.serialize_str(&date.format("%Y-%m-%d").to_string(),)
-//     }
-// }
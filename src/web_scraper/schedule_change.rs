@@ -0,0 +1,84 @@
+//! Notices when a facility's published hours change, so users can be told
+//! and shifts in the predictions explained.
+//!
+//! Changes are logged at `<prefix>/schedule_changes/<YYYY-MM-DD-HH-MM>`.
+
+use std::collections::BTreeSet;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use super::{schedule::Schedule, timing::Timing};
+
+const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+/// The hours of one day before and after a change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DayChange {
+    /// A weekday (`Monday`) for the usual hours, or a date (`2023-12-25`)
+    /// for date-specific hours.
+    pub day: String,
+    /// `None` when the day was not listed, e.g. a newly announced holiday.
+    pub before: Option<Timing>,
+    pub after: Option<Timing>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleChange {
+    /// UK time the new hours were first scraped.
+    pub detected_at: NaiveDateTime,
+    pub week_start: NaiveDate,
+    pub changes: Vec<DayChange>,
+}
+
+impl ScheduleChange {
+    /// What changed from `previous` to `current`, or `None` if nothing did.
+    /// Date-specific hours that have already passed are ignored, so
+    /// holidays dropping off the page are not reported.
+    pub fn detect(previous: &Schedule, current: &Schedule, detected_at: NaiveDateTime) -> Option<Self> {
+        let mut changes = Vec::new();
+        for (index, day) in WEEKDAYS.iter().enumerate() {
+            let before = previous.get_timings().get(index);
+            let after = current.get_timings().get(index);
+            if before != after {
+                changes.push(DayChange {
+                    day: day.to_string(),
+                    before: before.cloned(),
+                    after: after.cloned(),
+                });
+            }
+        }
+
+        let dates: BTreeSet<&NaiveDate> = previous
+            .get_overrides()
+            .keys()
+            .chain(current.get_overrides().keys())
+            .filter(|date| **date >= detected_at.date())
+            .collect();
+        for date in dates {
+            let before = previous.get_overrides().get(date);
+            let after = current.get_overrides().get(date);
+            if before != after {
+                changes.push(DayChange {
+                    day: date.to_string(),
+                    before: before.cloned(),
+                    after: after.cloned(),
+                });
+            }
+        }
+
+        if changes.is_empty() {
+            return None;
+        }
+        Some(Self {
+            detected_at,
+            week_start: *current.get_week_start(),
+            changes,
+        })
+    }
+
+    /// Key of this change in the change log.
+    pub fn key(&self) -> String {
+        self.detected_at.format("%Y-%m-%d-%H-%M").to_string()
+    }
+}
@@ -0,0 +1,65 @@
+use scraper::Selector;
+use serde::Deserialize;
+
+use super::error::ExtractError;
+
+/// Where the data lives on the gym website.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Selectors {
+    /// Elements that may hold the occupancy. The most specific one
+    /// containing `occupancy_label` is used.
+    pub occupancy: String,
+    /// Text just before the percentage, e.g. `Occupancy: 42%`.
+    pub occupancy_label: String,
+    /// One element per weekday, Monday first.
+    pub schedule: String,
+    /// Date-specific hours such as bank holidays, one `<date>: <hours>` element each.
+    pub exceptions: Option<String>,
+    /// Element holding the number of people, e.g. `37 of 120 people`.
+    pub headcount: Option<String>,
+    /// Element holding the capacity, when the headcount does not give it, e.g. `Capacity: 120`.
+    pub capacity: Option<String>,
+}
+
+impl Default for Selectors {
+    fn default() -> Self {
+        Self {
+            occupancy: "body *".to_string(),
+            occupancy_label: "Occupancy:".to_string(),
+            schedule: "dd.paired-values-list__value".to_string(),
+            exceptions: None,
+            headcount: None,
+            capacity: None,
+        }
+    }
+}
+
+/// `Selectors` parsed once, up front.
+#[derive(Debug, Clone)]
+pub struct CompiledSelectors {
+    pub occupancy: Selector,
+    pub schedule: Selector,
+    pub exceptions: Option<Selector>,
+    pub headcount: Option<Selector>,
+    pub capacity: Option<Selector>,
+}
+
+impl Selectors {
+    pub fn compile(&self) -> Result<CompiledSelectors, ExtractError> {
+        Ok(CompiledSelectors {
+            occupancy: Self::parse(&self.occupancy)?,
+            schedule: Self::parse(&self.schedule)?,
+            exceptions: self.exceptions.as_deref().map(Self::parse).transpose()?,
+            headcount: self.headcount.as_deref().map(Self::parse).transpose()?,
+            capacity: self.capacity.as_deref().map(Self::parse).transpose()?,
+        })
+    }
+
+    fn parse(selector: &str) -> Result<Selector, ExtractError> {
+        Selector::parse(selector).map_err(|err| ExtractError::InvalidSelector {
+            selector: selector.to_string(),
+            reason: err.to_string(),
+        })
+    }
+}
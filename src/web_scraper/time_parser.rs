@@ -0,0 +1,104 @@
+//! Reads the times of day written on opening-hours pages, which are not
+//! consistent: `6am`, `6:30 am`, `6.30 a.m.`, `06:30`, `18:00`, `Noon`,
+//! `12 noon`, `12 midnight`, ...
+
+use std::{error::Error, fmt, sync::LazyLock};
+
+use chrono::NaiveTime;
+use regex::Regex;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeError {
+    Empty,
+    /// Not a time in any format we know.
+    Unrecognised(String),
+    /// Looks like a time but is not one, e.g. `13pm` or `25:00`.
+    OutOfRange(String),
+    /// A bare hour such as `6`, which could be morning or evening.
+    Ambiguous(String),
+}
+
+impl fmt::Display for TimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "No time given"),
+            Self::Unrecognised(input) => write!(f, "{:?} is not a time", input),
+            Self::OutOfRange(input) => write!(f, "{:?} is out of range", input),
+            Self::Ambiguous(input) => write!(f, "{:?} could be am or pm", input),
+        }
+    }
+}
+
+impl Error for TimeError {}
+
+/// `<hour>[:<minute>] [am|pm|noon|midnight]`, once dots and spaces are tidied.
+static TIME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\d{1,2})(?:[:.](\d{2}))?\s*(am|pm|noon|midday|midnight)?$").unwrap()
+});
+
+/// 24-hour times without a separator, e.g. `0630`.
+static COMPACT_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\d{2})(\d{2})$").unwrap());
+
+/// Parses one time of day. Midnight is `00:00`, so a closing time of
+/// `12 midnight` or `1am` is earlier than its opening, see `Interval`.
+pub fn parse_time(input: &str) -> Result<NaiveTime, TimeError> {
+    let normalised = normalise(input);
+    let out_of_range = || TimeError::OutOfRange(input.trim().to_string());
+    let time = |hour: u32, minute: u32| NaiveTime::from_hms_opt(hour, minute, 0).ok_or_else(out_of_range);
+
+    match normalised.as_str() {
+        "" => return Err(TimeError::Empty),
+        "noon" | "midday" => return time(12, 0),
+        "midnight" => return time(0, 0),
+        _ => (),
+    }
+
+    if let Some(captures) = COMPACT_REGEX.captures(&normalised) {
+        return twenty_four_hour(captures[1].parse().unwrap(), captures[2].parse().unwrap()).ok_or_else(out_of_range);
+    }
+
+    let captures = TIME_REGEX
+        .captures(&normalised)
+        .ok_or_else(|| TimeError::Unrecognised(input.trim().to_string()))?;
+    let hour: u32 = captures[1].parse().unwrap();
+    let minute: u32 = captures.get(2).map_or(0, |minute| minute.as_str().parse().unwrap());
+    match captures.get(3).map(|suffix| suffix.as_str()) {
+        Some(suffix @ ("am" | "pm")) => {
+            if !(1..=12).contains(&hour) {
+                return Err(out_of_range());
+            }
+            // 12am is midnight, 12pm is noon
+            let hour = match suffix {
+                "am" => hour % 12,
+                _ => hour % 12 + 12,
+            };
+            time(hour, minute)
+        }
+        Some("noon" | "midday") if hour == 12 && minute == 0 => time(12, 0),
+        Some("midnight") if hour == 12 && minute == 0 => time(0, 0),
+        Some(_) => Err(out_of_range()),
+        // 24-hour clock, which needs the minutes to be unambiguous
+        None if captures.get(2).is_some() => twenty_four_hour(hour, minute).ok_or_else(out_of_range),
+        None => Err(TimeError::Ambiguous(input.trim().to_string())),
+    }
+}
+
+/// `24:00` is accepted as midnight.
+fn twenty_four_hour(hour: u32, minute: u32) -> Option<NaiveTime> {
+    match (hour, minute) {
+        (24, 0) => NaiveTime::from_hms_opt(0, 0, 0),
+        _ => NaiveTime::from_hms_opt(hour, minute, 0),
+    }
+}
+
+/// Lowercase, `a.m.` -> `am`, single spaces.
+fn normalise(input: &str) -> String {
+    let lower = input.trim().to_lowercase();
+    let lower = lower
+        .replace("a.m.", "am")
+        .replace("p.m.", "pm")
+        .replace("a.m", "am")
+        .replace("p.m", "pm")
+        .replace("hrs", "");
+    lower.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -1,14 +1,84 @@
 use chrono::NaiveTime;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 
-#[derive(PartialEq, Debug, Serialize)]
+/// One session, open from `opening` until just before `closing`. A closing
+/// at or before the opening is after midnight, e.g. 06:00 to 01:00.
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Interval {
+    #[serde(with = "hhmm")]
+    opening: NaiveTime,
+    #[serde(with = "hhmm")]
+    closing: NaiveTime,
+}
+
+impl Interval {
+    pub fn new(opening: NaiveTime, closing: NaiveTime) -> Self {
+        Self { opening, closing }
+    }
+
+    pub fn get_opening(&self) -> NaiveTime {
+        self.opening
+    }
+
+    pub fn get_closing(&self) -> NaiveTime {
+        self.closing
+    }
+
+    pub fn closes_after_midnight(&self) -> bool {
+        self.closing <= self.opening
+    }
+
+    /// Whether the session is open at `time` on the day it starts.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        match self.closes_after_midnight() {
+            true => self.opening <= time,
+            false => self.opening <= time && time < self.closing,
+        }
+    }
+
+    /// Whether the session is still open at `time` on the following day.
+    pub fn contains_after_midnight(&self, time: NaiveTime) -> bool {
+        self.closes_after_midnight() && time < self.closing
+    }
+}
+
+/// A day's opening hours: closed, or one or more sessions.
+///
+/// `opening`/`closing` are kept as the first opening and last closing of
+/// the day so readers of the old single-session format still work.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredTiming")]
 pub struct Timing {
     #[serde(with = "naive_time_serialize")]
     opening: Option<NaiveTime>,
     #[serde(with = "naive_time_serialize")]
     closing: Option<NaiveTime>,
-    open: bool
+    open: bool,
+    sessions: Vec<Interval>,
+}
+
+/// `Timing` as stored, before closed days lose their placeholder times.
+/// Timings stored before `sessions` existed have a single session.
+#[derive(Deserialize)]
+struct StoredTiming {
+    #[serde(with = "naive_time_serialize")]
+    opening: Option<NaiveTime>,
+    #[serde(with = "naive_time_serialize")]
+    closing: Option<NaiveTime>,
+    open: bool,
+    #[serde(default)]
+    sessions: Vec<Interval>,
+}
+
+impl From<StoredTiming> for Timing {
+    fn from(stored: StoredTiming) -> Self {
+        match (stored.open, stored.opening, stored.closing) {
+            (true, _, _) if !stored.sessions.is_empty() => Self::sessions(stored.sessions),
+            (true, Some(opening), Some(closing)) => Self::open(opening, closing),
+            _ => Self::closed(),
+        }
+    }
 }
 
 impl Timing {
@@ -16,41 +86,91 @@ impl Timing {
         Self {
             opening: None,
             closing: None,
-            open: false
+            open: false,
+            sessions: Vec::new(),
         }
     }
 
     pub fn open(opening: NaiveTime, closing: NaiveTime) -> Self {
-        Self {
-            opening: Some(opening),
-            closing: Some(closing),
-            open: true
+        Self::sessions(vec![Interval::new(opening, closing)])
+    }
+
+    /// Open for each of `sessions`, e.g. 07:00-13:00 and 16:00-22:00.
+    pub fn sessions(mut sessions: Vec<Interval>) -> Self {
+        sessions.sort_by_key(|session| session.get_opening());
+        match (sessions.first(), sessions.last()) {
+            (Some(first), Some(last)) => Self {
+                opening: Some(first.opening),
+                closing: Some(last.closing),
+                open: true,
+                sessions,
+            },
+            _ => Self::closed(),
         }
     }
 
+    /// The first opening of the day.
     pub fn get_opening(&self) -> Option<NaiveTime> {
         self.opening
     }
 
+    /// The last closing of the day.
     pub fn get_closing(&self) -> Option<NaiveTime> {
         self.closing
     }
 
+    pub fn get_sessions(&self) -> &[Interval] {
+        &self.sessions
+    }
+
     pub fn is_open(&self) -> bool {
         self.open
     }
+
+    /// Whether `time` falls within one of the sessions. Time after midnight
+    /// belongs to the day before, see `Schedule::is_open_at`.
+    pub fn is_open_at(&self, time: NaiveTime) -> bool {
+        self.sessions.iter().any(|session| session.contains(time))
+    }
+
+    /// The start of the first session after `time`, e.g. the afternoon
+    /// session during a midday break.
+    pub fn next_opening_after(&self, time: NaiveTime) -> Option<NaiveTime> {
+        self.sessions
+            .iter()
+            .map(|session| session.get_opening())
+            .find(|opening| *opening > time)
+    }
 }
 
 
+// HHMM as a number
+mod hhmm {
+    use chrono::NaiveTime;
+    use serde::{self, de, Deserialize, Deserializer, Serializer};
+    pub fn serialize<S>(time: &NaiveTime, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_u32(time.format("%H%M").to_string().parse().unwrap())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error> where D: Deserializer<'de> {
+        let time = u32::deserialize(deserializer)?;
+        NaiveTime::from_hms_opt(time / 100, time % 100, 0)
+            .ok_or_else(|| de::Error::custom(format!("Invalid time: {}", time)))
+    }
+}
+
+// HHMM as a number, 0 when closed
 mod naive_time_serialize {
     use chrono::NaiveTime;
-    use serde::{self, Serializer};
+    use serde::{self, Deserializer, Serializer};
     pub fn serialize<S>(time: &Option<NaiveTime>, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        match time {
+            Some(time) => super::hhmm::serialize(time, serializer),
+            None => serializer.serialize_u32(0)
+        }
+    }
 
-        let s: u32 = match time {
-            Some(time) => time.format("%H%M").to_string().parse().unwrap(),
-            None => 0
-        };
-        serializer.serialize_u32(s)
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<NaiveTime>, D::Error> where D: Deserializer<'de> {
+        super::hhmm::deserialize(deserializer).map(Some)
     }
 }
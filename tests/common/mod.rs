@@ -0,0 +1,30 @@
+//! Helpers shared by the integration tests. Each test file only uses some.
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf};
+
+/// An empty directory under the system temp dir, unique to this test run.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A path under the system temp dir, unique to this test run, with nothing at it.
+/// `schedule.json` becomes `schedule-<pid>.json`.
+pub fn temp_file(name: &str) -> PathBuf {
+    let name = match name.split_once('.') {
+        Some((stem, extension)) => format!("{}-{}.{}", stem, std::process::id(), extension),
+        None => format!("{}-{}", name, std::process::id()),
+    };
+    let path = std::env::temp_dir().join(name);
+    let _ = fs::remove_file(&path);
+    path
+}
+
+/// A saved page from `tests/fixtures`.
+pub fn fixture(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(format!("{}.html", name));
+    fs::read_to_string(path).unwrap()
+}
@@ -0,0 +1,111 @@
+use gym_backend::{
+    config::{Config, ConfigError},
+    firebase::credentials::CredentialSource,
+    knn_regressor::metric::Metric,
+};
+
+const MINIMAL: &str = r#"
+[[scraper.facility]]
+name = "gym"
+prefix = "rs_data/"
+url = "https://sport.wp.st-andrews.ac.uk/"
+"#;
+
+fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+#[test]
+fn shipped_config_is_valid() {
+    let config = Config::from_toml(include_str!("../config.toml"), Vec::new()).unwrap();
+    assert_eq!(config.scraper.facilities[0].data_location(), "rs_data/data");
+    assert_eq!(config.sleeper.frequency, 300);
+    assert_eq!(config.regressor.k, 3);
+}
+
+#[test]
+fn defaults_fill_in_missing_sections() {
+    let config = Config::from_toml(MINIMAL, Vec::new()).unwrap();
+    let gym = &config.scraper.facilities[0];
+    assert_eq!(gym.prefix, "rs_data");
+    assert_eq!(gym.selectors.schedule, "dd.paired-values-list__value");
+    assert_eq!(gym.knn_data_path(), "./gym_knn_regressor.data");
+    assert_eq!(gym.outbox_path(), "./gym_outbox.journal");
+    assert_eq!(config.firebase.credentials, CredentialSource::File);
+    assert_eq!(config.firebase.service_account_file, "serviceAccountKey.json.secret");
+    assert_eq!(config.storage.local_store, None);
+    assert_eq!(gym.predict, Metric::Occupancy);
+    assert_eq!(gym.history_location(), "rs_data/data");
+    assert_eq!(gym.prediction_location(), "rs_data/prediction");
+}
+
+#[test]
+fn headcount_can_be_predicted() {
+    let config = Config::from_toml(
+        &format!("{}predict = \"headcount\"\n[scraper.facility.selectors]\nheadcount = \".count\"\n", MINIMAL),
+        Vec::new(),
+    )
+    .unwrap();
+    let gym = &config.scraper.facilities[0];
+    assert_eq!(gym.predict, Metric::Headcount);
+    assert_eq!(gym.history_location(), "rs_data/data/headcount");
+    assert_eq!(gym.prediction_location(), "rs_data/prediction/headcount");
+    assert_eq!(gym.knn_data_path(), "./gym_headcount_knn_regressor.data");
+    assert_eq!(gym.outbox_path(), "./gym_outbox.journal");
+}
+
+#[test]
+fn environment_overrides_the_file() {
+    let config = Config::from_toml(
+        MINIMAL,
+        vars(&[
+            ("GYM_SLEEPER__FREQUENCY", "600"),
+            ("GYM_FIREBASE__CREDENTIALS", "secret"),
+            ("GYM_STORAGE__LOCAL_STORE", "local_store.json"),
+            ("GYM_STORAGE__STATE_DIR", "/var/lib/gym"),
+            ("FIREBASE_DATABASE_EMULATOR_HOST", "127.0.0.1:9000"),
+            ("PATH", "/usr/bin"),
+        ]),
+    )
+    .unwrap();
+    assert_eq!(config.sleeper.frequency, 600);
+    assert_eq!(config.firebase.credentials, CredentialSource::Secret);
+    assert_eq!(config.storage.local_store.as_deref(), Some("local_store.json"));
+    assert_eq!(config.firebase.emulator_host.as_deref(), Some("127.0.0.1:9000"));
+    assert_eq!(config.scraper.facilities[0].knn_data_path(), "/var/lib/gym/gym_knn_regressor.data");
+
+    let err = Config::from_toml(MINIMAL, vars(&[("GYM_SLEEPER", "600")])).unwrap_err();
+    assert!(matches!(err, ConfigError::Env { .. }), "{}", err);
+    let err = Config::from_toml(MINIMAL, vars(&[("GYM_SLEEPER__FREQUENCY", "often")])).unwrap_err();
+    assert!(matches!(err, ConfigError::Parse(_)), "{}", err);
+}
+
+#[test]
+fn invalid_configs_are_explained() {
+    let invalid = |extra: &str| match Config::from_toml(&format!("{}{}", MINIMAL, extra), Vec::new()) {
+        Err(ConfigError::Invalid(reason)) => reason,
+        other => panic!("expected a validation error, got {:?}", other),
+    };
+    assert!(invalid("[sleeper]\nfrequency = 420\n").contains("sleeper.frequency"));
+    assert!(invalid("[regressor]\nk = 0\n").contains("regressor.k"));
+    assert!(invalid("[[scraper.facility]]\nname = \"gym\"\nprefix = \"pool\"\nurl = \"https://a.b/\"\n")
+        .contains("listed twice"));
+    assert!(invalid("[[scraper.facility]]\nname = \"pool\"\nprefix = \"rs_data\"\nurl = \"https://a.b/\"\n")
+        .contains("rs_data"));
+    assert!(invalid("[[scraper.facility]]\nname = \"pool\"\nprefix = \"pool\"\nurl = \"not a url\"\n")
+        .contains("bad URL"));
+    assert!(invalid(
+        "[[scraper.facility]]\nname = \"pool\"\nprefix = \"pool\"\nurl = \"https://a.b/\"\n[scraper.facility.selectors]\nschedule = \"dd[\"\n"
+    )
+    .contains("Invalid selector"));
+    assert!(invalid("predict = \"headcount\"\n").contains("headcount selector"));
+    assert!(invalid("[scraper.facility.selectors]\ncapacity = \".capacity\"\n").contains("headcount selector"));
+    assert!(invalid("[alerts]\nthreshold = 0\n").contains("alerts.threshold"));
+    assert!(invalid("[alerts.smtp]\nfrom = \"gym@example.com\"\nto = []\n").contains("alerts.smtp.to"));
+
+    // Typos are caught rather than ignored
+    let err = Config::from_toml(&format!("{}[sleeper]\nfrequncy = 300\n", MINIMAL), Vec::new()).unwrap_err();
+    assert!(err.to_string().contains("frequncy"), "{}", err);
+    let err = Config::from_toml("[sleeper]\nfrequency = 300\n", Vec::new()).unwrap_err();
+    assert!(err.to_string().contains("scraper"), "{}", err);
+}
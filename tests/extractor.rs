@@ -0,0 +1,66 @@
+//! Fetches pages from a local server, so the whole scrape runs as it does
+//! against the real site.
+
+use chrono::{NaiveTime, Weekday};
+use gym_backend::{
+    firebase::mock_server::MockServer,
+    web_scraper::{error::ExtractError, extractor::Extractor, selectors::Selectors},
+};
+
+#[tokio::test]
+async fn markup_changes_are_reported() {
+    let server = MockServer::start().await.unwrap();
+    server
+        .serve_page("/gym", "<html><body><p>Busy: 42%</p><ul><li>6:30 am to 10:30 pm</li></ul></body></html>")
+        .await;
+    let mut extractor =
+        Extractor::with_selectors(format!("{}gym", server.url()), "Mozilla/5.0".to_string(), Selectors::default())
+            .unwrap();
+    extractor.scrape().await.unwrap();
+
+    assert!(matches!(extractor.scrape_occupancy(), Err(ExtractError::MissingLabel { .. })));
+    assert!(matches!(extractor.scrape_schedule(), Err(ExtractError::MissingElement { .. })));
+
+    let invalid = Selectors {
+        schedule: "dd[".to_string(),
+        ..Selectors::default()
+    };
+    assert!(matches!(
+        Extractor::with_selectors(server.url(), "Mozilla/5.0".to_string(), invalid),
+        Err(ExtractError::InvalidSelector { .. })
+    ));
+}
+
+#[tokio::test]
+async fn schedule_can_come_from_another_page() {
+    let server = MockServer::start().await.unwrap();
+    server.serve_page("/pool", "<p>Occupancy: 7%</p>").await;
+    let day = r#"<dd class="paired-values-list__value">7:00 am to 9:00 pm</dd>"#;
+    server.serve_page("/pool/hours", &format!("<dl>{}</dl>", day.repeat(7))).await;
+
+    let mut extractor =
+        Extractor::with_selectors(format!("{}pool", server.url()), "Mozilla/5.0".to_string(), Selectors::default())
+            .unwrap()
+            .with_schedule_url(format!("{}pool/hours", server.url()));
+    extractor.scrape().await.unwrap();
+    assert_eq!(extractor.scrape_occupancy().unwrap(), 7);
+    let schedule = extractor.scrape_schedule().unwrap();
+    let monday = schedule.get_timings_from_weekday(Weekday::Mon);
+    assert_eq!(monday.get_opening(), NaiveTime::from_hms_opt(7, 0, 0));
+}
+
+#[test]
+fn a_bad_exception_line_does_not_cost_the_schedule() {
+    let day = r#"<dd class="paired-values-list__value">6:30 am to 10:30 pm</dd>"#;
+    let html = format!("<dl>{}</dl><ul><li>25/12/2023: CLOSED</li><li>Holiday hours TBC</li></ul>", day.repeat(7));
+    let selectors = Selectors {
+        exceptions: Some("li".to_string()),
+        ..Selectors::default()
+    };
+    let extractor = Extractor::from_html_with_selectors(&html, selectors).unwrap();
+    let schedule = extractor.scrape_schedule().unwrap();
+    assert_eq!(schedule.get_overrides().len(), 1);
+    let skipped = extractor.skipped_exceptions();
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].to_string(), r#"Could not read date-specific hours from "Holiday hours TBC""#);
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::fs;
+
+use chrono::Duration;
+use gym_backend::{
+    core_functions::uk_datetime_now,
+    firebase::mock_server::MockServer,
+    web_scraper::{
+        error::ExtractError,
+        extractor::Extractor,
+        fetcher::{page_name, FileFetcher, Fetcher, HttpFetcher, Recorder, Replayer},
+    },
+};
+use tokio::net::TcpListener;
+
+#[test]
+fn page_names_are_file_names() {
+    assert_eq!(page_name("https://sport.wp.st-andrews.ac.uk/"), "sport_wp_st_andrews_ac_uk");
+    assert_eq!(page_name("http://127.0.0.1:9000/gym?x=1"), "127_0_0_1_9000_gym_x_1");
+}
+
+#[tokio::test]
+async fn pages_can_be_read_from_disk() {
+    let dir = common::temp_dir("file-fetcher");
+    let url = "https://sport.wp.st-andrews.ac.uk/";
+    let fetcher = FileFetcher::new(&dir);
+    fs::write(fetcher.path(url), common::fixture("normal")).unwrap();
+
+    let selectors = Default::default();
+    let mut extractor = Extractor::with_selectors(url.to_string(), String::new(), selectors)
+        .unwrap()
+        .with_fetcher(fetcher);
+    extractor.scrape().await.unwrap();
+    assert_eq!(extractor.scrape_occupancy().unwrap(), 42);
+
+    // A page that was never saved
+    let mut extractor = extractor.with_fetcher(FileFetcher::new(dir.join("missing")));
+    assert!(matches!(extractor.scrape().await, Err(ExtractError::Page { .. })));
+}
+
+#[tokio::test]
+async fn recorded_pages_can_be_replayed() {
+    let server = MockServer::start().await.unwrap();
+    server.serve_page("/gym", &common::fixture("normal")).await;
+    let url = format!("{}gym", server.url());
+    let dir = common::temp_dir("recorder");
+
+    let extractor = Extractor::with_selectors(url.clone(), "Mozilla/5.0".to_string(), Default::default()).unwrap();
+    let recorder = Recorder::new(extractor.fetcher().clone(), &dir);
+    let mut extractor = extractor.with_fetcher(recorder);
+    extractor.scrape().await.unwrap();
+    assert_eq!(extractor.scrape_occupancy().unwrap(), 42);
+    let recordings: Vec<_> = fs::read_dir(dir.join(page_name(&url))).unwrap().collect();
+    assert_eq!(recordings.len(), 1);
+
+    // The site changes, the recording does not
+    server.serve_page("/gym", &common::fixture("missing_occupancy")).await;
+    let now = uk_datetime_now::now().naive_local();
+    let mut extractor = extractor.with_fetcher(Replayer::new(&dir, now + Duration::minutes(1)));
+    extractor.scrape().await.unwrap();
+    assert_eq!(extractor.scrape_occupancy().unwrap(), 42);
+
+    // Nothing was recorded that long ago
+    extractor.fetcher_mut().set_time(now - Duration::days(1));
+    assert!(matches!(extractor.scrape().await, Err(ExtractError::NoRecording { .. })));
+}
+
+#[tokio::test]
+async fn hung_connections_time_out() {
+    // Accepts connections but never answers
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/gym", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            connections.push(stream);
+        }
+    });
+
+    let fetcher = HttpFetcher::new("Mozilla/5.0".to_string()).with_timeout(std::time::Duration::from_millis(200));
+    assert!(matches!(fetcher.fetch(&url).await, Err(ExtractError::Network(_))));
+    server.abort();
+}
@@ -0,0 +1,269 @@
+mod common;
+
+use std::{collections::HashMap, path::Path};
+
+use chrono::{Datelike, Duration, NaiveDate};
+use gym_backend::{
+    core_functions::{get_start_of_week, weekday_matcher},
+    firebase::{credentials::Credentials, firebase::Firebase, mock_server::MockServer},
+    knn_regressor::{data::Data, regressor::Regressor},
+    store::{backend::Store, outbox::Outbox, query::Query},
+    web_scraper::{extractor::Extractor, schedule::Schedule, selectors::Selectors},
+};
+use futures_util::{future::join_all, StreamExt};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+async fn emulator() -> (MockServer, Firebase) {
+    let server = MockServer::start().await.unwrap();
+    let firebase = Firebase::emulator(&server.url(), "gym-test");
+    (server, firebase)
+}
+
+/// A service account whose tokens come from `server`, which issues them for `expires_in` seconds.
+async fn service_account(expires_in: i64) -> (MockServer, Firebase) {
+    let server = MockServer::start().await.unwrap();
+    server.issue_tokens("/token", expires_in).await;
+    let key = json!({
+        "client_email": "gym-backend@example.iam.gserviceaccount.com",
+        "private_key": include_str!("fixtures/service_key.pem"),
+        "token_uri": format!("{}token", server.url()),
+    });
+    let credentials = Credentials::ServiceAccountJson(key.to_string());
+    let firebase = Firebase::with_credentials(credentials, server.url()).unwrap();
+    (server, firebase)
+}
+
+#[tokio::test]
+async fn concurrent_callers_share_one_token_exchange() {
+    let (server, firebase) = service_account(3600).await;
+    let results = join_all((0..10).map(|_| firebase.handle_auth_token())).await;
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(server.posted("/token").await.len(), 1);
+
+    // Still well within its hour
+    firebase.handle_auth_token().await.unwrap();
+    assert_eq!(server.posted("/token").await.len(), 1);
+}
+
+#[tokio::test]
+async fn tokens_are_refreshed_within_five_minutes_of_expiry() {
+    // Outside the margin, so reused
+    let (server, firebase) = service_account(6 * 60).await;
+    for _ in 0..3 {
+        firebase.handle_auth_token().await.unwrap();
+    }
+    assert_eq!(server.posted("/token").await.len(), 1);
+
+    // Inside the margin from the start, so every call refreshes
+    let (server, firebase) = service_account(4 * 60).await;
+    for _ in 0..3 {
+        firebase.handle_auth_token().await.unwrap();
+    }
+    assert_eq!(server.posted("/token").await.len(), 3);
+}
+
+#[tokio::test]
+async fn writes_rejected_with_a_revoked_token_stay_queued() {
+    let (server, firebase) = service_account(3600).await;
+    let path = common::temp_file("outbox-revoked.journal");
+    let mut outbox = Outbox::open(path.to_str().unwrap()).await;
+    firebase.set("counter", "1").await.unwrap();
+    server.revoke_token("mock-token-1").await;
+
+    let batch = firebase.batch().set("counter", json!(2));
+    outbox.enqueue(batch.into_write()).await.unwrap();
+    assert!(outbox.flush(&firebase).await.is_err());
+    assert_eq!(outbox.len(), 1);
+    assert!(!Path::new(&outbox.dead_letter_path()).exists());
+
+    // The rejected token is replaced on the next attempt
+    assert_eq!(outbox.flush(&firebase).await.unwrap(), 1);
+    assert_eq!(server.posted("/token").await.len(), 2);
+    assert_eq!(server.data("counter").await, json!(2));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn set_update_get_delete_round_trip() {
+    let (server, firebase) = emulator().await;
+
+    firebase.set("rs_data/data/2023-10-02/0", r#"{"0630":12}"#).await.unwrap();
+    firebase.update("rs_data/data/2023-10-02/0", r#"{"0635":15}"#).await.unwrap();
+    firebase.set("rs_data/data/2023-10-02/1", r#"{"0630":3}"#).await.unwrap();
+
+    // Consecutive integer keys come back as an array, like Firebase
+    let week: Value = serde_json::from_str(&firebase.get("rs_data/data/2023-10-02").await.unwrap()).unwrap();
+    assert_eq!(week, json!([{ "0630": 12, "0635": 15 }, { "0630": 3 }]));
+
+    firebase.delete("rs_data/data/2023-10-02/0").await.unwrap();
+    assert_eq!(server.data("rs_data/data/2023-10-02").await, json!({ "1": { "0630": 3 } }));
+
+    // Deleting the last child removes the parents too
+    firebase.delete("rs_data/data/2023-10-02/1").await.unwrap();
+    assert_eq!(server.data("rs_data").await, Value::Null);
+}
+
+#[tokio::test]
+async fn queries_filter_on_the_server() {
+    let (_server, firebase) = emulator().await;
+    firebase
+        .set("day", r#"{"0630":10,"0700":20,"0730":30,"0800":40}"#)
+        .await
+        .unwrap();
+
+    let query = Query::new().order_by_key().start_at("0700").end_at("0730");
+    let range: Value = firebase.query_as("day", &query).await.unwrap();
+    assert_eq!(range, json!({ "0700": 20, "0730": 30 }));
+
+    let busiest: Value = firebase
+        .query_as("day", &Query::new().order_by_value().limit_to_last(1))
+        .await
+        .unwrap();
+    assert_eq!(busiest, json!({ "0800": 40 }));
+
+    let keys: Value = firebase.query_as("", &Query::new().shallow()).await.unwrap();
+    assert_eq!(keys, json!({ "day": true }));
+}
+
+#[tokio::test]
+async fn outbox_batches_are_written_together() {
+    let (server, firebase) = emulator().await;
+    let path = common::temp_file("outbox.journal");
+    let mut outbox = Outbox::open(path.to_str().unwrap()).await;
+
+    let batch = firebase
+        .batch()
+        .set("rs_data/data/2023-10-02/0/0630", json!(12))
+        .set("rs_data/data/latest/data", json!({ "2023-10-02-06-30": 12 }));
+    outbox.enqueue(batch.into_write()).await.unwrap();
+    assert_eq!(outbox.flush(&firebase).await.unwrap(), 1);
+    assert!(outbox.is_empty());
+
+    assert_eq!(server.data("rs_data/data/2023-10-02/0").await, json!({ "0630": 12 }));
+    assert_eq!(server.data("rs_data/data/latest/data").await, json!({ "2023-10-02-06-30": 12 }));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn compare_and_swap_retries_after_a_conflict() {
+    let (server, firebase) = emulator().await;
+    firebase.set("counter", "1").await.unwrap();
+
+    let (_, stale_etag) = firebase.get_with_etag("counter").await.unwrap();
+    firebase.set("counter", "2").await.unwrap();
+    assert!(firebase.set_if_match("counter", "5", &stale_etag).await.is_err());
+
+    let result = firebase
+        .compare_and_swap("counter", |current| {
+            let current: u32 = current.parse().unwrap();
+            Some((current + 1).to_string())
+        })
+        .await
+        .unwrap();
+    assert_eq!(result, "3");
+    assert_eq!(server.data("counter").await, json!(3));
+}
+
+#[tokio::test]
+async fn scrape_upload_predict() {
+    let (server, firebase) = emulator().await;
+    server
+        .serve_page(
+            "/gym",
+            r#"<html><body>
+            <p>Occupancy: 42%</p>
+            <dl>
+            <dd class="paired-values-list__value">6:30 am to 10:30 pm</dd>
+            <dd class="paired-values-list__value">6:30 am to 10:30 pm</dd>
+            <dd class="paired-values-list__value">6:30 am to 10:30 pm</dd>
+            <dd class="paired-values-list__value">6:30 am to 10:30 pm</dd>
+            <dd class="paired-values-list__value">6:30 am to 10:30 pm</dd>
+            <dd class="paired-values-list__value">8:00 am to 8:00 pm</dd>
+            <dd class="paired-values-list__value">8:00 am to 8:00 pm</dd>
+            </dl></body></html>"#,
+        )
+        .await;
+    // Scrape
+    let mut extractor =
+        Extractor::with_selectors(format!("{}gym", server.url()), "Mozilla/5.0".to_string(), Selectors::default())
+            .unwrap();
+    extractor.scrape().await.unwrap();
+    let occupancy = extractor.scrape_occupancy().unwrap();
+    let schedule = extractor.scrape_schedule().unwrap();
+    assert_eq!(occupancy, 42);
+
+    // Upload a sample for last Monday
+    let today = NaiveDate::from_ymd_opt(2023, 10, 9).unwrap();
+    let last_week = get_start_of_week::get(today - Duration::days(7));
+    let location = format!("rs_data/data/{}/{}", last_week, weekday_matcher::get_num(last_week.weekday()));
+    let schedule_location = format!("rs_data/data/schedule/{}", last_week);
+    let requests_before = server.requests().await.len();
+    firebase
+        .batch()
+        .update(&location, json!({ "0630": occupancy, "0700": occupancy }).as_object().unwrap().clone())
+        .set(&schedule_location, serde_json::to_value(&schedule).unwrap())
+        .commit()
+        .await
+        .unwrap();
+    // Both locations in one request
+    assert_eq!(server.requests().await.len(), requests_before + 1);
+    let samples: HashMap<String, u8> = firebase.get_as(&location).await.unwrap();
+    assert_eq!(samples, [("0630".to_string(), 42), ("0700".to_string(), 42)].into());
+    let stored: Schedule = firebase.get_as(&schedule_location).await.unwrap();
+    assert_eq!(stored, schedule);
+
+    // The typed writes round-trip too
+    let samples: HashMap<&str, u8> = [("0730", occupancy)].into();
+    firebase.update_as(&location, &samples).await.unwrap();
+    firebase.set_as(&schedule_location, &schedule).await.unwrap();
+    assert_eq!(server.data(&format!("{}/0730", location)).await, json!(42));
+    let stored: Schedule = firebase.get_as(&schedule_location).await.unwrap();
+    assert_eq!(stored, schedule);
+
+    // Predict this Monday from it
+    let data = Data::new(&firebase, "rs_data/data", 1, today).await.unwrap();
+    let regressor = Regressor::new(data, 1);
+    let predictions = regressor.predict_range(630, 700, 5, 0);
+    assert_eq!(predictions.len(), 7);
+    // Each weighted term is truncated, so the average may land just below
+    assert!(predictions.iter().all(|point| (41..=42).contains(&point.get_occupancy())));
+}
+
+#[tokio::test]
+async fn database_secret_is_sent_as_auth_param() {
+    let server = MockServer::start().await.unwrap();
+    let firebase =
+        Firebase::with_credentials(Credentials::DatabaseSecret("legacy-secret".to_string()), server.url()).unwrap();
+
+    firebase.set("rs_data/data/latest/data", r#"{"2023-10-02-06-30":12}"#).await.unwrap();
+    assert_eq!(server.data("rs_data/data/latest/data").await, json!({ "2023-10-02-06-30": 12 }));
+    let requests = server.requests().await;
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].query.contains(&("auth".to_string(), "legacy-secret".to_string())), "{:?}", requests[0]);
+    assert_eq!(requests[0].authorization, None);
+    assert!(!format!("{:?}", firebase).contains("legacy-secret"));
+}
+
+#[tokio::test]
+async fn stream_errors_do_not_leak_the_database_secret() {
+    // Starts a stream, then hangs up part way through
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = [0; 4096];
+        let _ = stream.read(&mut request).await.unwrap();
+        let response = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: 1000\r\n\r\nevent: put\n";
+        stream.write_all(response.as_bytes()).await.unwrap();
+    });
+
+    let firebase =
+        Firebase::with_credentials(Credentials::DatabaseSecret("legacy-secret".to_string()), url).unwrap();
+    let mut events = Box::pin(firebase.listen("rs_data/data").await.unwrap());
+    let err = events.next().await.unwrap().unwrap_err();
+    assert!(!format!("{} {:?}", err, err).contains("legacy-secret"), "{:?}", err);
+}
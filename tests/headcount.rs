@@ -0,0 +1,49 @@
+use gym_backend::web_scraper::{
+    extractor::Extractor,
+    headcount::{parse_number, Headcount},
+    selectors::Selectors,
+};
+
+fn headcount(people: u16, capacity: Option<u16>) -> Option<Headcount> {
+    Some(Headcount { people, capacity })
+}
+
+#[test]
+fn counts_as_written_on_occupancy_pages() {
+    let table = [
+        ("50 of 120 people", headcount(50, Some(120))),
+        ("50 out of 120", headcount(50, Some(120))),
+        ("50/120", headcount(50, Some(120))),
+        ("People in the gym: 50 / 120", headcount(50, Some(120))),
+        ("1,050 of 1,200", headcount(1050, Some(1200))),
+        ("50 people", headcount(50, None)),
+        ("Currently 0 people", headcount(0, None)),
+        ("No one here", None),
+        ("", None),
+    ];
+    for (text, expected) in table {
+        assert_eq!(Headcount::parse(text), expected, "{:?}", text);
+    }
+    assert_eq!(parse_number("Capacity: 120"), Some(120));
+    assert_eq!(parse_number("Capacity: lots"), None);
+}
+
+#[test]
+fn capacity_can_come_from_its_own_element() {
+    let html = r#"<p>Occupancy: 40%</p><p class="count">48 people</p><p class="capacity">Capacity: 120</p>"#;
+    let selectors = Selectors {
+        headcount: Some(".count".to_string()),
+        capacity: Some(".capacity".to_string()),
+        ..Selectors::default()
+    };
+    let extractor = Extractor::from_html_with_selectors(html, selectors.clone()).unwrap();
+    assert_eq!(extractor.scrape_headcount().unwrap(), headcount(48, Some(120)));
+
+    let html = r#"<p>Occupancy: 40%</p><p class="count">48 people</p><p class="capacity">Capacity: TBC</p>"#;
+    let extractor = Extractor::from_html_with_selectors(html, selectors).unwrap();
+    let err = extractor.scrape_headcount().unwrap_err();
+    assert_eq!(err.to_string(), r#"Could not read a headcount from "Capacity: TBC""#);
+
+    // Not configured
+    assert_eq!(Extractor::from_html(html).scrape_headcount().unwrap(), None);
+}
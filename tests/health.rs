@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use gym_backend::{
+    firebase::mock_server::MockServer,
+    health::{
+        monitor::{check_headcount, check_occupancy, AlertKind, Field, HealthMonitor},
+        notifier::{Notifier, NotifyError, SmtpNotifier, WebhookNotifier},
+    },
+    web_scraper::{error::ExtractError, extractor::Extractor, headcount::Headcount, selectors::Selectors},
+};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+
+fn missing() -> Result<u8, ExtractError> {
+    Err(ExtractError::MissingElement { selector: "body *".to_string() })
+}
+
+#[test]
+fn alerts_once_at_the_threshold_and_on_recovery() {
+    let mut monitor = HealthMonitor::new("gym", 3);
+    assert_eq!(monitor.observe(Field::Occupancy, &missing()), None);
+    assert_eq!(monitor.observe(Field::Occupancy, &missing()), None);
+    // Other fields are counted separately
+    assert_eq!(monitor.observe(Field::Schedule, &missing()), None);
+
+    let alert = monitor.observe(Field::Occupancy, &missing()).unwrap();
+    assert_eq!((alert.field, alert.kind, alert.consecutive), (Field::Occupancy, AlertKind::Failing, 3));
+    assert!(alert.to_string().starts_with("gym occupancy has failed 3 times in a row"), "{}", alert);
+    assert_eq!(monitor.observe(Field::Occupancy, &missing()), None);
+    assert_eq!(monitor.failures(Field::Occupancy), 4);
+
+    let alert = monitor.observe(Field::Occupancy, &Ok(40)).unwrap();
+    assert_eq!((alert.kind, alert.consecutive), (AlertKind::Recovered, 4));
+    assert_eq!(monitor.failures(Field::Occupancy), 0);
+    // A failure that never reached the threshold recovers quietly
+    assert_eq!(monitor.observe(Field::Schedule, &Ok(())), None);
+}
+
+#[test]
+fn implausible_values_are_failures() {
+    assert_eq!(check_occupancy(100).unwrap(), 100);
+    assert!(matches!(check_occupancy(140), Err(ExtractError::Anomaly(_))));
+
+    let headcount = |people, capacity| Some(Headcount { people, capacity });
+    assert!(check_headcount(None).is_ok());
+    assert!(check_headcount(headcount(300, None)).is_ok());
+    assert!(check_headcount(headcount(120, Some(120))).is_ok());
+    let err = check_headcount(headcount(130, Some(120))).unwrap_err();
+    assert_eq!(err.to_string(), "Implausible headcount of 130 of 120");
+
+    let mut monitor = HealthMonitor::new("gym", 1);
+    let alert = monitor.observe(Field::Occupancy, &check_occupancy(140)).unwrap();
+    assert_eq!(alert.reason, "Implausible occupancy of 140%");
+}
+
+#[tokio::test]
+async fn a_short_schedule_raises_an_alert() {
+    let server = MockServer::start().await.unwrap();
+    let day = r#"<dd class="paired-values-list__value">6:30 am to 10:30 pm</dd>"#;
+    server.serve_page("/gym", &format!("<p>Occupancy: 42%</p><dl>{}</dl>", day.repeat(5))).await;
+    let mut extractor =
+        Extractor::with_selectors(format!("{}gym", server.url()), "Mozilla/5.0".to_string(), Selectors::default())
+            .unwrap();
+    extractor.scrape().await.unwrap();
+
+    let mut monitor = HealthMonitor::new("gym", 2);
+    assert_eq!(monitor.observe(Field::Schedule, &extractor.scrape_schedule()), None);
+    let alert = monitor.observe(Field::Schedule, &extractor.scrape_schedule()).unwrap();
+    assert_eq!(alert.kind, AlertKind::Failing);
+    assert!(alert.reason.ends_with("found 5"), "{}", alert.reason);
+    // The rest of the page is still read
+    assert_eq!(monitor.observe(Field::Occupancy, &extractor.scrape_occupancy()), None);
+}
+
+#[tokio::test]
+async fn webhooks_receive_the_alert() {
+    let server = MockServer::start().await.unwrap();
+    let mut monitor = HealthMonitor::new("pool", 1);
+    let alert = monitor.observe(Field::Schedule, &check_occupancy(140)).unwrap();
+
+    let notifier = WebhookNotifier::new(format!("{}hooks/alerts", server.url()));
+    notifier.notify(&alert).await.unwrap();
+
+    let posted = server.posted("/hooks/alerts").await;
+    assert_eq!(posted.len(), 1);
+    let body: Value = serde_json::from_str(&posted[0]).unwrap();
+    assert_eq!(body["text"], alert.to_string());
+    assert_eq!(body["alert"]["field"], "schedule");
+    assert_eq!(body["alert"]["kind"], "failing");
+}
+
+#[tokio::test]
+async fn emails_are_sent_over_smtp() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    // Accepts one message and returns everything the client sent
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = BufReader::new(stream);
+        stream.get_mut().write_all(b"220 localhost\r\n").await.unwrap();
+        let mut received = String::new();
+        let mut in_data = false;
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.unwrap() == 0 {
+                return received;
+            }
+            received.push_str(&line);
+            let reply: &[u8] = match line.trim_end() {
+                "." if in_data => {
+                    in_data = false;
+                    b"250 queued\r\n"
+                }
+                _ if in_data => continue,
+                "DATA" => {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                }
+                "QUIT" => b"221 bye\r\n",
+                _ => b"250 ok\r\n",
+            };
+            stream.get_mut().write_all(reply).await.unwrap();
+        }
+    });
+
+    let mut monitor = HealthMonitor::new("gym", 1);
+    let alert = monitor.observe(Field::Page, &missing()).unwrap();
+    let notifier = SmtpNotifier::new(address, "gym@example.com".to_string(), vec!["me@example.com".to_string()]);
+    notifier.notify(&alert).await.unwrap();
+
+    let received = server.await.unwrap();
+    assert!(received.contains("MAIL FROM:<gym@example.com>"), "{}", received);
+    assert!(received.contains("RCPT TO:<me@example.com>"), "{}", received);
+    assert!(received.contains("Subject: [gym-backend] gym page"), "{}", received);
+    assert!(received.contains(&alert.to_string()), "{}", received);
+}
+
+#[tokio::test]
+async fn unresponsive_notifiers_time_out() {
+    // Accepts connections but never says anything
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let server = tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            connections.push(stream);
+        }
+    });
+
+    let mut monitor = HealthMonitor::new("gym", 1);
+    let alert = monitor.observe(Field::Page, &missing()).unwrap();
+    let limit = Duration::from_millis(200);
+
+    let smtp = SmtpNotifier::new(address.clone(), "gym@example.com".to_string(), vec!["me@example.com".to_string()])
+        .with_timeout(limit);
+    assert!(matches!(smtp.notify(&alert).await, Err(NotifyError::Timeout(_))));
+
+    let webhook = WebhookNotifier::new(format!("http://{}/hooks/alerts", address)).with_timeout(limit);
+    assert!(matches!(webhook.notify(&alert).await, Err(NotifyError::Network(_))));
+    server.abort();
+}
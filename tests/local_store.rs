@@ -0,0 +1,48 @@
+mod common;
+
+use std::fs;
+
+use gym_backend::store::{backend::Store, local_store::LocalStore};
+use serde_json::{json, Value};
+
+async fn read(store: &LocalStore, location: &str) -> Value {
+    serde_json::from_str(&store.get(location).await.unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn writes_are_kept_on_disk() {
+    let path = common::temp_file("local-store.json");
+    let store = LocalStore::new(path.to_str().unwrap()).await;
+    store.set("rs_data/data/2023-10-02/0", r#"{"0630":12}"#).await.unwrap();
+    store.update("rs_data/data/2023-10-02/0", r#"{"0635":15}"#).await.unwrap();
+    store.set("rs_data/data/2023-10-02/1", r#"{"0630":3}"#).await.unwrap();
+    store.delete("rs_data/data/2023-10-02/1").await.unwrap();
+
+    let store = LocalStore::new(path.to_str().unwrap()).await;
+    assert_eq!(read(&store, "rs_data/data/2023-10-02/0").await, json!({ "0630": 12, "0635": 15 }));
+    assert_eq!(read(&store, "rs_data/data/2023-10-09").await, Value::Null);
+    let _ = fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn arrays_come_back_as_arrays() {
+    let path = common::temp_file("local-store-arrays.json");
+    // Hand-written, with an array and a null in it
+    fs::write(&path, r#"{"rs_data":{"data":{"2023-10-02":[{"0630":12},null,{"0630":3}]}}}"#).unwrap();
+    let store = LocalStore::new(path.to_str().unwrap()).await;
+
+    assert_eq!(read(&store, "rs_data/data/2023-10-02").await, json!([{ "0630": 12 }, null, { "0630": 3 }]));
+    // Stored keyed by index, so single days can be read
+    assert_eq!(read(&store, "rs_data/data/2023-10-02/2").await, json!({ "0630": 3 }));
+    let _ = fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn unreadable_files_start_empty() {
+    let path = common::temp_file("local-store-broken.json");
+    fs::write(&path, "{ not json").unwrap();
+    let store = LocalStore::new(path.to_str().unwrap()).await;
+    assert_eq!(read(&store, "day").await, Value::Null);
+    assert!(store.set("day", "{ not json").await.is_err());
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use std::{error::Error, fmt, fs, path::PathBuf};
+
+use gym_backend::store::{
+    backend::Store,
+    outbox::{Outbox, Write},
+    query::Query,
+};
+use tokio::sync::Mutex;
+
+#[derive(Debug)]
+struct TestError {
+    transient: bool,
+}
+
+impl fmt::Display for TestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failure", if self.transient { "transient" } else { "permanent" })
+    }
+}
+
+impl Error for TestError {}
+
+impl From<serde_json::Error> for TestError {
+    fn from(_: serde_json::Error) -> Self {
+        Self { transient: false }
+    }
+}
+
+/// Records the locations written, failing the writes queued in `failures` first.
+#[derive(Default)]
+struct RecordingStore {
+    written: Mutex<Vec<String>>,
+    /// `Some(transient)` fails the next write.
+    failures: Mutex<Vec<Option<bool>>>,
+}
+
+impl RecordingStore {
+    async fn write(&self, location: &str) -> Result<(), TestError> {
+        let mut failures = self.failures.lock().await;
+        if !failures.is_empty() {
+            if let Some(transient) = failures.remove(0) {
+                return Err(TestError { transient });
+            }
+        }
+        self.written.lock().await.push(location.to_string());
+        Ok(())
+    }
+
+    async fn written(&self) -> Vec<String> {
+        self.written.lock().await.clone()
+    }
+}
+
+impl Store for RecordingStore {
+    type Error = TestError;
+
+    fn is_transient(err: &TestError) -> bool {
+        err.transient
+    }
+
+    async fn get(&self, _: &str) -> Result<String, TestError> {
+        Ok("null".to_string())
+    }
+
+    async fn query(&self, _: &str, _: &Query) -> Result<String, TestError> {
+        Ok("null".to_string())
+    }
+
+    async fn set(&self, location: &str, _: &str) -> Result<(), TestError> {
+        self.write(location).await
+    }
+
+    async fn update(&self, location: &str, _: &str) -> Result<(), TestError> {
+        self.write(location).await
+    }
+
+    async fn delete(&self, location: &str) -> Result<(), TestError> {
+        self.write(location).await
+    }
+}
+
+fn journal(name: &str) -> PathBuf {
+    let path = common::temp_file(&format!("{}.journal", name));
+    let _ = fs::remove_file(path.with_extension("journal.dead"));
+    path
+}
+
+fn set(location: &str) -> Write {
+    Write::Set {
+        location: location.to_string(),
+        data: "1".to_string(),
+    }
+}
+
+fn pending(id: u64, location: &str) -> String {
+    format!(r#"{{"pending":{{"id":{},"write":{{"op":"set","location":"{}","data":"1"}}}}}}"#, id, location)
+}
+
+#[tokio::test]
+async fn a_partial_last_line_is_dropped_before_appending() {
+    let path = journal("outbox-torn");
+    let torn = pending(1, "b");
+    fs::write(&path, format!("{}\n{}", pending(0, "a"), &torn[..torn.len() / 2])).unwrap();
+
+    let mut outbox = Outbox::open(path.to_str().unwrap()).await;
+    assert_eq!(outbox.len(), 1);
+    outbox.enqueue(set("c")).await.unwrap();
+    assert!(fs::read_to_string(&path).unwrap().ends_with("}\n"));
+
+    // The write queued after the crash survives a restart
+    let mut outbox = Outbox::open(path.to_str().unwrap()).await;
+    assert_eq!(outbox.len(), 2);
+    let store = RecordingStore::default();
+    assert_eq!(outbox.flush(&store).await.unwrap(), 2);
+    assert_eq!(store.written().await, ["a", "c"]);
+    let _ = fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn done_writes_are_not_replayed() {
+    let path = journal("outbox-done");
+    let done = r#"{"done":{"id":0}}"#;
+    fs::write(&path, format!("{}\n{}\n{}\n", pending(0, "a"), pending(1, "b"), done)).unwrap();
+
+    let mut outbox = Outbox::open(path.to_str().unwrap()).await;
+    assert_eq!(outbox.len(), 1);
+    // Ids carry on after the recovered ones
+    outbox.enqueue(set("c")).await.unwrap();
+    assert!(fs::read_to_string(&path).unwrap().contains(r#""id":2"#));
+
+    let store = RecordingStore::default();
+    assert_eq!(outbox.flush(&store).await.unwrap(), 2);
+    assert_eq!(store.written().await, ["b", "c"]);
+    // Everything was sent, so the journal is emptied
+    assert_eq!(fs::read_to_string(&path).unwrap(), "");
+    assert_eq!(Outbox::open(path.to_str().unwrap()).await.len(), 0);
+    let _ = fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn writes_stay_in_order_after_a_transient_failure() {
+    let path = journal("outbox-order");
+    let mut outbox = Outbox::open(path.to_str().unwrap()).await;
+    for location in ["a", "b", "c"] {
+        outbox.enqueue(set(location)).await.unwrap();
+    }
+
+    let store = RecordingStore::default();
+    store.failures.lock().await.extend([None, Some(true)]);
+    assert!(outbox.flush(&store).await.unwrap_err().transient);
+    assert_eq!(store.written().await, ["a"]);
+    assert_eq!(outbox.len(), 2);
+
+    // Still queued after a restart, in order
+    let mut outbox = Outbox::open(path.to_str().unwrap()).await;
+    assert_eq!(outbox.flush(&store).await.unwrap(), 2);
+    assert_eq!(store.written().await, ["a", "b", "c"]);
+    let _ = fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn writes_rejected_for_good_are_dead_lettered() {
+    let path = journal("outbox-dead");
+    let mut outbox = Outbox::open(path.to_str().unwrap()).await;
+    for location in ["a", "b", "c"] {
+        outbox.enqueue(set(location)).await.unwrap();
+    }
+
+    let store = RecordingStore::default();
+    store.failures.lock().await.push(Some(false));
+    assert_eq!(outbox.flush(&store).await.unwrap(), 2);
+    assert_eq!(store.written().await, ["b", "c"]);
+    assert!(outbox.is_empty());
+
+    let dead = fs::read_to_string(outbox.dead_letter_path()).unwrap();
+    assert_eq!(dead, "{\"op\":\"set\",\"location\":\"a\",\"data\":\"1\"}\n");
+    let _ = fs::remove_file(outbox.dead_letter_path());
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use std::fs;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use gym_backend::{
+    facility::Facility,
+    store::{backend::Store, local_store::LocalStore},
+    web_scraper::{archive::PageArchive, error::ExtractError, reextract::reextract, selectors::Selectors},
+};
+use serde_json::{json, Value};
+
+fn at(time: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(&format!("2023-10-02 {}", time), "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+#[tokio::test]
+async fn identical_pages_are_stored_once_a_day() {
+    let dir = common::temp_dir("page-archive");
+    let archive = PageArchive::new(&dir);
+    let url = "https://sport.wp.st-andrews.ac.uk/";
+
+    assert!(archive.add(url, &common::fixture("normal"), at("07:00:01")).await.unwrap());
+    assert!(!archive.add(url, &common::fixture("normal"), at("07:05:01")).await.unwrap());
+    assert!(archive.add(url, &common::fixture("closed_days"), at("07:10:01")).await.unwrap());
+
+    let date = NaiveDate::from_ymd_opt(2023, 10, 2).unwrap();
+    assert_eq!(archive.dates().await.unwrap(), vec![date]);
+    let pages = archive.pages(date).await.unwrap();
+    assert_eq!(pages.len(), 3);
+    assert_eq!(pages[0].hash, pages[1].hash);
+    assert_eq!(pages[1].fetched_at, at("07:05:01"));
+    assert_eq!(archive.read(&pages[1]).await.unwrap(), common::fixture("normal"));
+
+    let blobs = fs::read_dir(dir.join("2023-10-02"))
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "gz"))
+        .count();
+    assert_eq!(blobs, 2);
+}
+
+#[tokio::test]
+async fn history_can_be_reextracted() {
+    let dir = common::temp_dir("reextract");
+    let store_path = dir.join("store.json");
+    // 07:05 was recorded wrongly by an older parser
+    fs::write(&store_path, json!({ "rs_data": { "data": { "2023-10-02": [{ "0700": 42, "0705": 0 }] } } }).to_string()).unwrap();
+    let store = LocalStore::new(store_path.to_str().unwrap()).await;
+
+    let facility = Facility {
+        name: "gym".to_string(),
+        prefix: "rs_data".to_string(),
+        url: "https://sport.wp.st-andrews.ac.uk/".to_string(),
+        schedule_url: None,
+        selectors: Default::default(),
+        predict: Default::default(),
+        state_dir: dir.to_string_lossy().to_string(),
+    };
+    let archive = PageArchive::new(dir.join("pages"));
+    // Before opening time
+    archive.add(&facility.url, &common::fixture("normal"), at("06:00:01")).await.unwrap();
+    archive.add(&facility.url, &common::fixture("normal"), at("07:05:01")).await.unwrap();
+    archive.add(&facility.url, &common::fixture("missing_occupancy"), at("07:10:01")).await.unwrap();
+    // Another facility's page
+    archive.add("https://example.com/", &common::fixture("closed_days"), at("07:15:01")).await.unwrap();
+
+    let date = NaiveDate::from_ymd_opt(2023, 10, 2).unwrap();
+    let result = reextract(&store, &facility, &archive, date, date).await.unwrap();
+    assert_eq!(result.samples, 1);
+    assert_eq!(result.outside_hours, 1);
+    assert_eq!(result.failures.len(), 1);
+    assert!(matches!(result.failures[0], (time, ExtractError::MissingLabel { .. }) if time == at("07:10:01")));
+
+    let day: Value = store.get_as("rs_data/data/2023-10-02/0").await.unwrap();
+    assert_eq!(day, json!({ "0700": 42, "0705": 42 }));
+
+    // Nothing archived in range
+    let earlier = NaiveDate::from_ymd_opt(2023, 9, 25).unwrap();
+    let result = reextract(&store, &facility, &archive, earlier, earlier).await.unwrap();
+    assert_eq!(result.samples, 0);
+}
+
+#[tokio::test]
+async fn schedules_on_their_own_page_are_paired_with_each_fetch() {
+    let dir = common::temp_dir("reextract-schedule-page");
+    let store = LocalStore::new(dir.join("store.json").to_str().unwrap()).await;
+    let facility = Facility {
+        name: "pool".to_string(),
+        prefix: "rs_pool".to_string(),
+        url: "https://example.com/pool".to_string(),
+        schedule_url: Some("https://example.com/pool/hours".to_string()),
+        selectors: Selectors {
+            exceptions: Some("li".to_string()),
+            ..Selectors::default()
+        },
+        predict: Default::default(),
+        state_dir: dir.to_string_lossy().to_string(),
+    };
+    let day = r#"<dd class="paired-values-list__value">6:30 am to 10:30 pm</dd>"#;
+    // No year, so only right if read in the week it was fetched
+    let hours = format!("<dl>{}</dl><ul><li>Monday 2 October: 7:03 am to 10:30 pm</li></ul>", day.repeat(7));
+    let archive = PageArchive::new(dir.join("pages"));
+    // Archived in the order the scraper fetches them
+    for time in ["07:00:01", "07:05:01"] {
+        archive.add(&facility.url, "<p>Occupancy: 42%</p>", at(time)).await.unwrap();
+        archive.add(facility.schedule_url.as_ref().unwrap(), &hours, at(time)).await.unwrap();
+    }
+
+    let date = NaiveDate::from_ymd_opt(2023, 10, 2).unwrap();
+    let result = reextract(&store, &facility, &archive, date, date).await.unwrap();
+    assert!(result.failures.is_empty(), "{:?}", result.failures);
+    // Before the later opening that day
+    assert_eq!(result.outside_hours, 1);
+    assert_eq!(result.samples, 1);
+    let day: Value = store.get_as("rs_pool/data/2023-10-02/0").await.unwrap();
+    assert_eq!(day, json!({ "0705": 42 }));
+}
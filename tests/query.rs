@@ -0,0 +1,98 @@
+mod common;
+
+use std::fs;
+
+use gym_backend::store::{backend::Store, local_store::LocalStore, query::Query};
+use serde_json::{json, Value};
+
+async fn store(name: &str, data: Value) -> LocalStore {
+    let path = common::temp_file(&format!("{}.json", name));
+    fs::write(&path, data.to_string()).unwrap();
+    let store = LocalStore::new(path.to_str().unwrap()).await;
+    let _ = fs::remove_file(&path);
+    store
+}
+
+async fn query(store: &LocalStore, location: &str, query: Query) -> Value {
+    serde_json::from_str(&store.query(location, &query).await.unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn keys_are_filtered_in_order() {
+    let store = store("query-keys", json!({ "day": { "0630": 10, "0700": 20, "0730": 30, "0800": 40 } })).await;
+
+    let range = query(&store, "day", Query::new().order_by_key().start_at("0700").end_at("0730")).await;
+    assert_eq!(range, json!({ "0700": 20, "0730": 30 }));
+    // Filters without an orderBy order by key, as the REST parameters do
+    assert_eq!(query(&store, "day", Query::new().start_at("0730")).await, json!({ "0730": 30, "0800": 40 }));
+    assert_eq!(query(&store, "day", Query::new().limit_to_first(1)).await, json!({ "0630": 10 }));
+    assert_eq!(query(&store, "day", Query::new().equal_to("0800")).await, json!({ "0800": 40 }));
+    // No filters reads everything
+    assert_eq!(query(&store, "day", Query::new()).await, json!({ "0630": 10, "0700": 20, "0730": 30, "0800": 40 }));
+    assert_eq!(query(&store, "missing", Query::new().limit_to_first(1)).await, Value::Null);
+}
+
+#[tokio::test]
+async fn integer_keys_sort_numerically_before_other_keys() {
+    let store = store("query-integers", json!({ "week": { "10": "c", "9": "b", "a": "d", "1": "a" } })).await;
+    let first = query(&store, "week", Query::new().order_by_key().limit_to_first(2)).await;
+    assert_eq!(first, json!({ "1": "a", "9": "b" }));
+    let last = query(&store, "week", Query::new().order_by_key().limit_to_last(2)).await;
+    assert_eq!(last, json!({ "10": "c", "a": "d" }));
+}
+
+#[tokio::test]
+async fn values_and_children_can_be_ordered_on() {
+    let store = store(
+        "query-values",
+        json!({
+            "day": { "0630": 40, "0700": 10, "0730": 30, "0800": 20 },
+            "facilities": {
+                "gym": { "stats": { "occupancy": 60 } },
+                "pool": { "stats": { "occupancy": 15 } },
+                "track": {},
+                "hall": { "stats": { "occupancy": 35 } },
+            },
+        }),
+    )
+    .await;
+
+    let busiest = query(&store, "day", Query::new().order_by_value().limit_to_last(2)).await;
+    assert_eq!(busiest, json!({ "0630": 40, "0730": 30 }));
+    let quiet = query(&store, "day", Query::new().order_by_value().end_at(20)).await;
+    assert_eq!(quiet, json!({ "0700": 10, "0800": 20 }));
+
+    let busy = query(&store, "facilities", Query::new().order_by_child("stats/occupancy").start_at(30)).await;
+    assert_eq!(busy, json!({ "gym": { "stats": { "occupancy": 60 } }, "hall": { "stats": { "occupancy": 35 } } }));
+}
+
+#[tokio::test]
+async fn shallow_reads_return_only_the_keys() {
+    let store = store("query-shallow", json!({ "rs_data": { "data": { "2023-10-02": [{ "0630": 1 }] }, "version": 2 } })).await;
+    let keys = query(&store, "rs_data", Query::new().shallow()).await;
+    assert_eq!(keys, json!({ "data": true, "version": 2 }));
+}
+
+#[test]
+fn queries_round_trip_through_rest_parameters() {
+    let query = Query::new().order_by_child("stats/occupancy").start_at(30).end_at("z").limit_to_last(3);
+    let params = query.to_params();
+    assert_eq!(
+        params,
+        [
+            ("orderBy", r#""stats/occupancy""#.to_string()),
+            ("startAt", "30".to_string()),
+            ("endAt", r#""z""#.to_string()),
+            ("limitToLast", "3".to_string()),
+        ]
+    );
+    let parsed = Query::from_params(params.iter().map(|(name, value)| (*name, value.as_str()))).unwrap();
+    assert_eq!(parsed, query);
+
+    // Filters need an orderBy
+    assert_eq!(Query::new().limit_to_first(1).to_params()[0], ("orderBy", r#""$key""#.to_string()));
+    assert_eq!(Query::new().shallow().to_params(), [("shallow", "true".to_string())]);
+    assert!(Query::from_params([("limitToFirst", "-1")]).is_err());
+    assert!(Query::from_params([("startAt", "not json")]).is_err());
+    assert_eq!(Query::from_params([("print", "pretty")]).unwrap(), Query::new());
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::io::Read;
+
+use chrono::NaiveDate;
+use flate2::read::GzDecoder;
+use gym_backend::store::{backend::Store, local_store::LocalStore, retention::Retention};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn old_weeks_are_archived_then_deleted() {
+    let dir = common::temp_dir("retention");
+    let store_path = dir.join("store.json");
+    std::fs::write(
+        &store_path,
+        json!({
+            "rs_data": {
+                "data": {
+                    "2023-08-28": [{ "0630": 5 }],
+                    "2023-09-18": [{ "0630": 10 }],
+                    "2023-10-02": [{ "0630": 20 }],
+                    "latest": { "data": { "2023-10-02-06-30": 20 } },
+                    "schedule": { "2023-08-28": { "timings": [{ "opening": 0, "closing": 0, "open": false }] }, "2023-10-02": { "timings": [{ "opening": 0, "closing": 0, "open": false }] } }
+                },
+                "prediction": { "2023-08-28": [{ "630": 6 }], "2023-10-02": [{ "630": 19 }] }
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let store = LocalStore::new(store_path.to_str().unwrap()).await;
+
+    // Horizon of one week, but the regressor needs three
+    let retention = Retention::new(1, dir.join("archive"));
+    let today = NaiveDate::from_ymd_opt(2023, 10, 4).unwrap();
+    assert_eq!(retention.cutoff(today, 3), NaiveDate::from_ymd_opt(2023, 9, 11).unwrap());
+
+    let pruned = retention.prune(&store, "rs_data", today, 3).await.unwrap();
+    assert_eq!(pruned, vec![NaiveDate::from_ymd_opt(2023, 8, 28).unwrap()]);
+
+    let data: Value = store.get_as("rs_data").await.unwrap();
+    assert_eq!(data["data"]["2023-08-28"], Value::Null);
+    assert_eq!(data["data"]["schedule"]["2023-08-28"], Value::Null);
+    assert_eq!(data["prediction"]["2023-08-28"], Value::Null);
+    assert_eq!(data["data"]["2023-09-18"], json!([{ "0630": 10 }]));
+    assert_eq!(data["data"]["latest"]["data"], json!({ "2023-10-02-06-30": 20 }));
+
+    let mut archived = String::new();
+    let file = std::fs::File::open(retention.archive_path("rs_data", pruned[0])).unwrap();
+    GzDecoder::new(file).read_to_string(&mut archived).unwrap();
+    let archived: Value = serde_json::from_str(&archived).unwrap();
+    assert_eq!(archived["data"], json!([{ "0630": 5 }]));
+    assert_eq!(archived["data/schedule"], json!({ "timings": [{ "opening": 0, "closing": 0, "open": false }] }));
+    assert_eq!(archived["prediction"], json!([{ "630": 6 }]));
+
+    // Nothing left to do
+    assert!(retention.prune(&store, "rs_data", today, 3).await.unwrap().is_empty());
+    let _ = std::fs::remove_dir_all(&dir);
+}
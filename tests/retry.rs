@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use gym_backend::firebase::{error::FirebaseError, retry::RetryPolicy};
+use reqwest::StatusCode;
+
+fn status(code: u16) -> FirebaseError {
+    FirebaseError::HttpStatus(StatusCode::from_u16(code).unwrap())
+}
+
+#[tokio::test]
+async fn only_transient_failures_are_retried() {
+    let policy = RetryPolicy::default();
+    // Nothing listens on port 1
+    let network = FirebaseError::Network(reqwest::get("http://127.0.0.1:1/").await.unwrap_err());
+    assert!(policy.should_retry(&network, 1));
+    assert!(policy.should_retry(&status(500), 1));
+    assert!(policy.should_retry(&status(503), 1));
+    assert!(policy.should_retry(&status(429), 1));
+
+    assert!(!policy.should_retry(&status(400), 1));
+    assert!(!policy.should_retry(&status(404), 1));
+    assert!(!policy.should_retry(&FirebaseError::PermissionDenied(StatusCode::UNAUTHORIZED), 1));
+    let decode = serde_json::from_str::<u8>("{").unwrap_err();
+    assert!(!policy.should_retry(&FirebaseError::JsonDecode(decode), 1));
+
+    // Attempts are capped
+    assert!(policy.should_retry(&status(500), policy.max_attempts - 1));
+    assert!(!policy.should_retry(&status(500), policy.max_attempts));
+    assert!(!RetryPolicy::none().should_retry(&network, 1));
+
+    let policy = RetryPolicy {
+        retry_on_server_error: false,
+        retry_on_too_many_requests: false,
+        ..RetryPolicy::default()
+    };
+    assert!(!policy.should_retry(&status(500), 1));
+    assert!(!policy.should_retry(&status(429), 1));
+}
+
+#[test]
+fn delays_grow_exponentially_up_to_the_maximum() {
+    let policy = RetryPolicy {
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_secs(1),
+        jitter: false,
+        ..RetryPolicy::default()
+    };
+    let delays: Vec<u128> = (1..=6).map(|attempt| policy.delay(attempt).as_millis()).collect();
+    assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+    // Does not overflow on absurd attempt counts
+    assert_eq!(policy.delay(u32::MAX), Duration::from_secs(1));
+}
+
+#[test]
+fn jitter_stays_between_zero_and_the_exponential_delay() {
+    let policy = RetryPolicy {
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_secs(1),
+        jitter: true,
+        ..RetryPolicy::default()
+    };
+    for attempt in 1..=8 {
+        let limit = Duration::from_millis(100 << (attempt - 1)).min(Duration::from_secs(1));
+        for _ in 0..50 {
+            assert!(policy.delay(attempt) <= limit, "attempt {}", attempt);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use chrono::{NaiveDate, NaiveTime};
+use gym_backend::{
+    store::{backend::Store, local_store::LocalStore},
+    web_scraper::{error::ExtractError, schedule::Schedule, schedule_change::ScheduleChange, timing::Timing},
+};
+use serde_json::json;
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2023, month, day).unwrap()
+}
+
+fn week(entries: [&str; 7]) -> Schedule {
+    Schedule::from_entries(entries).unwrap().with_week_start(date(10, 2))
+}
+
+#[tokio::test]
+async fn schedules_load_from_their_week() {
+    let path = common::temp_file("schedule.json");
+    let store = LocalStore::new(path.to_str().unwrap()).await;
+
+    let mut schedule = week(["6:30 am to 10:30 pm"; 7]);
+    schedule.set_override(date(10, 4), Timing::closed());
+    store.set_as("rs_data/data/schedule/2023-10-02", &schedule).await.unwrap();
+    assert_eq!(Schedule::load(&store, "rs_data/data", date(10, 2)).await.unwrap(), Some(schedule));
+    assert_eq!(Schedule::load(&store, "rs_data/data", date(10, 9)).await.unwrap(), None);
+
+    // Written before schedules kept their week or sessions
+    let timing = json!({ "opening": 630, "closing": 2230, "open": true });
+    let closed = json!({ "opening": 0, "closing": 0, "open": false });
+    store
+        .set_as(
+            "rs_data/data/schedule/2023-09-25",
+            &json!({ "timings": [timing, timing, timing, timing, timing, closed, closed] }),
+        )
+        .await
+        .unwrap();
+    let old = Schedule::load(&store, "rs_data/data", date(9, 25)).await.unwrap().unwrap();
+    assert_eq!(old.get_week_start(), &date(9, 25));
+    let saturday = old.timing_on(date(9, 30));
+    assert!(!saturday.is_open());
+    assert!(old.timing_on(date(9, 25)).is_open_at(NaiveTime::from_hms_opt(6, 30, 0).unwrap()));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn changes_to_the_hours_are_detected() {
+    let now = date(10, 2).and_hms_opt(7, 5, 0).unwrap();
+    let before = week(["6:30 am to 10:30 pm"; 7]);
+    assert_eq!(ScheduleChange::detect(&before, &before.clone(), now), None);
+
+    let mut after = week([
+        "6:30 am to 10:30 pm",
+        "6:30 am to 10:30 pm",
+        "7:00 am to 1:00 pm, 4:00 pm to 10:00 pm",
+        "6:30 am to 10:30 pm",
+        "6:30 am to 10:30 pm",
+        "6:30 am to 10:30 pm",
+        "CLOSED",
+    ]);
+    after.set_override(date(12, 25), Timing::closed());
+    // Already passed, not worth telling anyone about
+    after.set_override(date(9, 1), Timing::closed());
+
+    let change = ScheduleChange::detect(&before, &after, now).unwrap();
+    assert_eq!(change.key(), "2023-10-02-07-05");
+    let days: Vec<&str> = change.changes.iter().map(|change| change.day.as_str()).collect();
+    assert_eq!(days, ["Wednesday", "Sunday", "2023-12-25"]);
+    assert_eq!(change.changes[1].after, Some(Timing::closed()));
+    assert_eq!(change.changes[2].before, None);
+
+    // Stored and read back through the change log
+    let stored = serde_json::to_value(&change).unwrap();
+    assert_eq!(stored["detected_at"], json!("2023-10-02T07:05:00"));
+    assert_eq!(serde_json::from_value::<ScheduleChange>(stored).unwrap(), change);
+}
+
+#[test]
+fn hours_earlier_in_the_week_are_already_past() {
+    // Wednesday
+    let now = date(10, 4).and_hms_opt(7, 5, 0).unwrap();
+    let before = week(["6:30 am to 10:30 pm"; 7]);
+    let mut after = before.clone();
+    after.set_override(date(10, 2), Timing::closed());
+    assert_eq!(ScheduleChange::detect(&before, &after, now), None);
+
+    after.set_override(date(10, 4), Timing::closed());
+    let change = ScheduleChange::detect(&before, &after, now).unwrap();
+    let days: Vec<&str> = change.changes.iter().map(|change| change.day.as_str()).collect();
+    assert_eq!(days, ["2023-10-04"]);
+}
+
+#[test]
+fn dates_without_a_year_are_the_nearest_across_new_year() {
+    let december = Schedule::from_entries(["6:30 am to 10:30 pm"; 7])
+        .unwrap()
+        .with_week_start(date(12, 18));
+    let mut schedule = december.clone();
+    let skipped = schedule.add_exceptions(["Monday 25 December: CLOSED", "Monday 1 January: CLOSED", "1st January: CLOSED"]);
+    assert!(skipped.is_empty(), "{:?}", skipped);
+    let next_year = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    assert_eq!(schedule.get_overrides().keys().copied().collect::<Vec<_>>(), [date(12, 25), next_year]);
+
+    // Still on the page in the new year
+    let mut schedule = december.with_week_start(next_year);
+    assert!(schedule.add_exceptions(["Monday 25 December: CLOSED"]).is_empty());
+    assert_eq!(schedule.get_overrides().keys().copied().collect::<Vec<_>>(), [date(12, 25)]);
+}
+
+#[test]
+fn unreadable_exceptions_are_skipped() {
+    let mut schedule = week(["6:30 am to 10:30 pm"; 7]);
+    let skipped = schedule.add_exceptions([
+        "Tuesday 3 October: CLOSED",
+        "Friday 3 October: CLOSED",
+        "Gym closed for maintenance",
+        "Wednesday 4 October: whenever",
+    ]);
+    assert_eq!(skipped.len(), 3);
+    assert!(matches!(skipped[0], ExtractError::InvalidException(_)));
+    assert_eq!(schedule.get_overrides().keys().copied().collect::<Vec<_>>(), [date(10, 3)]);
+}
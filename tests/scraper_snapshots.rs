@@ -0,0 +1,112 @@
+//! Runs the extractor over saved pages in `tests/fixtures` and compares the
+//! result with `tests/snapshots`. Set `UPDATE_SNAPSHOTS=1` to rewrite the
+//! snapshots after an intended change, then review the diff.
+
+mod common;
+
+use std::{env, fs, path::Path};
+
+use gym_backend::web_scraper::{error::ExtractError, extractor::Extractor, selectors::Selectors};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+fn outcome<T: Serialize>(result: Result<T, ExtractError>) -> Value {
+    match result {
+        Ok(value) => json!({ "ok": value }),
+        Err(err) => json!({ "error": err.to_string() }),
+    }
+}
+
+fn assert_snapshot(name: &str, extractor: &Extractor) {
+    let mut actual = json!({
+        "occupancy": outcome(extractor.scrape_occupancy()),
+        "schedule": outcome(extractor.scrape_schedule()),
+        "headcount": outcome(extractor.scrape_headcount()),
+    });
+    // The current week, so it differs from run to run
+    if let Some(schedule) = actual.pointer_mut("/schedule/ok").and_then(Value::as_object_mut) {
+        schedule.remove("week_start");
+    }
+    let actual = serde_json::to_string_pretty(&actual).unwrap() + "\n";
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{}.json", name));
+    if env::var("UPDATE_SNAPSHOTS").is_ok() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("No snapshot for {}, run with UPDATE_SNAPSHOTS=1", name));
+    assert_eq!(actual, expected, "{} no longer matches its snapshot", name);
+}
+
+#[test]
+fn normal() {
+    assert_snapshot("normal", &Extractor::from_html(&common::fixture("normal")));
+}
+
+#[test]
+fn closed_days() {
+    assert_snapshot("closed_days", &Extractor::from_html(&common::fixture("closed_days")));
+}
+
+#[test]
+fn missing_occupancy() {
+    assert_snapshot("missing_occupancy", &Extractor::from_html(&common::fixture("missing_occupancy")));
+}
+
+#[test]
+fn noon() {
+    assert_snapshot("noon", &Extractor::from_html(&common::fixture("noon")));
+}
+
+#[test]
+fn noon_words() {
+    assert_snapshot("noon_words", &Extractor::from_html(&common::fixture("noon_words")));
+}
+
+#[test]
+fn markup_change() {
+    assert_snapshot("markup_change", &Extractor::from_html(&common::fixture("markup_change")));
+}
+
+#[test]
+fn markup_change_with_new_selectors() {
+    let selectors = Selectors {
+        occupancy: ".occupancy-widget".to_string(),
+        occupancy_label: "Live occupancy".to_string(),
+        schedule: ".opening-hours__day time".to_string(),
+        ..Selectors::default()
+    };
+    let extractor = Extractor::from_html_with_selectors(&common::fixture("markup_change"), selectors).unwrap();
+    assert_snapshot("markup_change_with_new_selectors", &extractor);
+}
+
+#[test]
+fn split_sessions_and_exceptions() {
+    let selectors = Selectors {
+        exceptions: Some("ul.holiday-hours li".to_string()),
+        ..Selectors::default()
+    };
+    let extractor = Extractor::from_html_with_selectors(&common::fixture("exceptions"), selectors).unwrap();
+    assert_snapshot("split_sessions_and_exceptions", &extractor);
+}
+
+#[test]
+fn headcount() {
+    let selectors = Selectors {
+        headcount: Some(".occupancy__count".to_string()),
+        ..Selectors::default()
+    };
+    let extractor = Extractor::from_html_with_selectors(&common::fixture("headcount"), selectors).unwrap();
+    assert_snapshot("headcount", &extractor);
+}
+
+#[test]
+fn headcount_missing() {
+    let selectors = Selectors {
+        headcount: Some(".occupancy__count".to_string()),
+        ..Selectors::default()
+    };
+    let extractor = Extractor::from_html_with_selectors(&common::fixture("normal"), selectors).unwrap();
+    assert_snapshot("headcount_missing", &extractor);
+}
@@ -0,0 +1,99 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use gym_backend::{
+    sleeper::Sleeper,
+    web_scraper::{
+        schedule::Schedule,
+        timing::{Interval, Timing},
+    },
+};
+use serde_json::json;
+
+fn time(hour: u32, minute: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+}
+
+// 2023-10-02 is a Monday
+fn at(day: u32, hour: u32, minute: u32, second: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2023, 10, day).unwrap().and_hms_opt(hour, minute, second).unwrap()
+}
+
+fn sleeper() -> Sleeper {
+    let schedule = Schedule::from_entries([
+        "7:00 am to 1:00 pm, 4:00 pm to 10:00 pm",
+        "6:30 am to 10:30 pm",
+        "6:30 am to 10:30 pm",
+        "6:30 am to 10:30 pm",
+        "6:30 am to 10:00 pm",
+        "CLOSED",
+        "CLOSED",
+    ])
+    .unwrap();
+    Sleeper::new(300, 300, Some(schedule))
+}
+
+#[test]
+fn samples_every_frequency_while_open() {
+    let sleeper = sleeper();
+    assert_eq!(sleeper.next_wake(at(2, 7, 2, 30)), Some(at(2, 7, 5, 0)));
+    assert_eq!(sleeper.next_wake(at(2, 7, 5, 0)), Some(at(2, 7, 10, 0)));
+}
+
+#[test]
+fn waits_out_the_break_between_sessions() {
+    let sleeper = sleeper();
+    assert_eq!(sleeper.next_wake(at(2, 13, 0, 0)), Some(at(2, 16, 0, 0)));
+    assert_eq!(sleeper.next_wake(at(2, 5, 0, 0)), Some(at(2, 7, 0, 0)));
+}
+
+#[test]
+fn skips_closed_days() {
+    let sleeper = sleeper();
+    // Friday night to Monday morning
+    assert_eq!(sleeper.next_wake(at(6, 22, 0, 0)), Some(at(9, 7, 0, 0)));
+}
+
+#[test]
+fn date_specific_hours_win() {
+    let mut schedule = Schedule::from_entries(["6:30 am to 10:30 pm"; 7]).unwrap();
+    // Closed on Tuesday, late opening on Wednesday
+    schedule.set_override(NaiveDate::from_ymd_opt(2023, 10, 3).unwrap(), Timing::closed());
+    schedule.set_override(
+        NaiveDate::from_ymd_opt(2023, 10, 4).unwrap(),
+        Timing::sessions(vec![Interval::new(time(10, 0), time(16, 0))]),
+    );
+    assert!(!schedule.timing_on(NaiveDate::from_ymd_opt(2023, 10, 3).unwrap()).is_open_at(time(12, 0)));
+    assert!(schedule.timing_on(NaiveDate::from_ymd_opt(2023, 10, 5).unwrap()).is_open_at(time(7, 0)));
+
+    let sleeper = Sleeper::new(300, 300, Some(schedule));
+    assert_eq!(sleeper.next_wake(at(2, 22, 30, 0)), Some(at(4, 10, 0, 0)));
+}
+
+#[test]
+fn single_session_timings_still_load() {
+    let timing: Timing = serde_json::from_value(json!({ "opening": 630, "closing": 2230, "open": true })).unwrap();
+    assert_eq!(timing, Timing::open(time(6, 30), time(22, 30)));
+
+    let split = Timing::sessions(vec![
+        Interval::new(time(16, 0), time(22, 0)),
+        Interval::new(time(7, 0), time(13, 0)),
+    ]);
+    // The first opening and last closing are kept for older readers
+    let stored = serde_json::to_value(&split).unwrap();
+    assert_eq!(stored["opening"], json!(700));
+    assert_eq!(stored["closing"], json!(2200));
+    assert_eq!(serde_json::from_value::<Timing>(stored).unwrap(), split);
+}
+
+#[test]
+fn sessions_can_close_after_midnight() {
+    let schedule = Schedule::from_entries(["6am to 1am"; 7]).unwrap();
+    assert!(schedule.is_open_at(at(2, 23, 30, 0)));
+    // Still Monday's session
+    assert!(schedule.is_open_at(at(3, 0, 30, 0)));
+    assert!(!schedule.is_open_at(at(3, 1, 0, 0)));
+    assert!(!schedule.is_open_at(at(3, 5, 59, 0)));
+
+    let sleeper = Sleeper::new(300, 300, Some(schedule));
+    assert_eq!(sleeper.next_wake(at(3, 0, 57, 0)), Some(at(3, 1, 0, 0)));
+    assert_eq!(sleeper.next_wake(at(3, 1, 0, 0)), Some(at(3, 6, 0, 0)));
+}
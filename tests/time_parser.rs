@@ -0,0 +1,88 @@
+use chrono::NaiveTime;
+use gym_backend::web_scraper::time_parser::{parse_time, TimeError};
+
+fn time(hour: u32, minute: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+}
+
+#[test]
+fn times_as_written_on_the_site() {
+    let table = [
+        // 12-hour
+        ("6am", time(6, 0)),
+        ("6 am", time(6, 0)),
+        ("6AM", time(6, 0)),
+        ("6:30 am", time(6, 30)),
+        ("6:30am", time(6, 30)),
+        ("06:30 am", time(6, 30)),
+        ("6.30 am", time(6, 30)),
+        ("6.30 a.m.", time(6, 30)),
+        ("6.30 a.m", time(6, 30)),
+        ("6:30 A.M.", time(6, 30)),
+        ("10:30 pm", time(22, 30)),
+        ("10.30pm", time(22, 30)),
+        ("10 p.m.", time(22, 0)),
+        ("1am", time(1, 0)),
+        ("11:59 pm", time(23, 59)),
+        // Noon and midnight
+        ("12pm", time(12, 0)),
+        ("12:00 pm", time(12, 0)),
+        ("12.30 pm", time(12, 30)),
+        ("12am", time(0, 0)),
+        ("12:30 am", time(0, 30)),
+        ("Noon", time(12, 0)),
+        ("noon", time(12, 0)),
+        ("12 noon", time(12, 0)),
+        ("12noon", time(12, 0)),
+        ("12:00 noon", time(12, 0)),
+        ("Midday", time(12, 0)),
+        ("12 midday", time(12, 0)),
+        ("Midnight", time(0, 0)),
+        ("12 midnight", time(0, 0)),
+        ("12:00 midnight", time(0, 0)),
+        // 24-hour
+        ("06:30", time(6, 30)),
+        ("6:30", time(6, 30)),
+        ("6.30", time(6, 30)),
+        ("18:00", time(18, 0)),
+        ("18.45", time(18, 45)),
+        ("00:00", time(0, 0)),
+        ("23:59", time(23, 59)),
+        ("24:00", time(0, 0)),
+        ("0630", time(6, 30)),
+        ("2230", time(22, 30)),
+        ("18:00 hrs", time(18, 0)),
+        // Whitespace
+        ("  6:30   am ", time(6, 30)),
+        ("\n10:30 pm\t", time(22, 30)),
+    ];
+    for (input, expected) in table {
+        assert_eq!(parse_time(input), Ok(expected), "{:?}", input);
+    }
+}
+
+#[test]
+fn nonsense_is_a_typed_error() {
+    let table = [
+        ("", TimeError::Empty),
+        ("   ", TimeError::Empty),
+        ("CLOSED", TimeError::Unrecognised("CLOSED".to_string())),
+        ("late", TimeError::Unrecognised("late".to_string())),
+        ("6:3 am", TimeError::Unrecognised("6:3 am".to_string())),
+        ("6:30:00", TimeError::Unrecognised("6:30:00".to_string())),
+        ("123 am", TimeError::Unrecognised("123 am".to_string())),
+        ("13pm", TimeError::OutOfRange("13pm".to_string())),
+        ("0am", TimeError::OutOfRange("0am".to_string())),
+        ("6:60 am", TimeError::OutOfRange("6:60 am".to_string())),
+        ("25:00", TimeError::OutOfRange("25:00".to_string())),
+        ("24:30", TimeError::OutOfRange("24:30".to_string())),
+        ("2460", TimeError::OutOfRange("2460".to_string())),
+        ("6 noon", TimeError::OutOfRange("6 noon".to_string())),
+        ("12:30 midnight", TimeError::OutOfRange("12:30 midnight".to_string())),
+        ("6", TimeError::Ambiguous("6".to_string())),
+        ("12", TimeError::Ambiguous("12".to_string())),
+    ];
+    for (input, expected) in table {
+        assert_eq!(parse_time(input), Err(expected), "{:?}", input);
+    }
+}
@@ -0,0 +1,83 @@
+use gym_backend::store::tree;
+use serde_json::{json, Value};
+
+#[test]
+fn set_replaces_and_creates_parents() {
+    let mut root = json!({});
+    tree::set(&mut root, "rs_data/data/2023-10-02/0", json!({ "0630": 12 }));
+    assert_eq!(root, json!({ "rs_data": { "data": { "2023-10-02": { "0": { "0630": 12 } } } } }));
+    assert_eq!(tree::get(&root, "rs_data/data/2023-10-02/0/0630"), Some(&json!(12)));
+    assert_eq!(tree::get(&root, "/rs_data/data/"), tree::get(&root, "rs_data/data"));
+    assert_eq!(tree::get(&root, "rs_data/data/2023-10-09"), None);
+
+    // Setting replaces the whole node, unlike update
+    tree::set(&mut root, "rs_data/data/2023-10-02/0", json!({ "0635": 15 }));
+    assert_eq!(tree::get(&root, "rs_data/data/2023-10-02/0"), Some(&json!({ "0635": 15 })));
+
+    // A value in the way is replaced by the children written below it
+    tree::set(&mut root, "rs_data/data/2023-10-02/0/0635/extra", json!(1));
+    assert_eq!(tree::get(&root, "rs_data/data/2023-10-02/0/0635"), Some(&json!({ "extra": 1 })));
+}
+
+#[test]
+fn nulls_and_empty_objects_are_not_stored() {
+    let mut root = json!({});
+    tree::set(&mut root, "day", json!({ "0630": 10, "0700": null, "empty": {}, "nested": { "empty": {} } }));
+    assert_eq!(root, json!({ "day": { "0630": 10 } }));
+
+    tree::set(&mut root, "other", json!({}));
+    assert_eq!(tree::get(&root, "other"), None);
+
+    // Arrays are stored keyed by index
+    tree::set(&mut root, "week", json!([{ "0630": 1 }, null, { "0630": 3 }]));
+    assert_eq!(tree::get(&root, "week"), Some(&json!({ "0": { "0630": 1 }, "2": { "0630": 3 } })));
+}
+
+#[test]
+fn update_writes_each_child_path() {
+    let mut root = json!({ "day": { "0630": 10, "0700": 20 } });
+    let children = json!({ "0700": 25, "0730": 30, "0800": null }).as_object().unwrap().clone();
+    tree::update(&mut root, "day", children);
+    assert_eq!(root, json!({ "day": { "0630": 10, "0700": 25, "0730": 30 } }));
+
+    // Keys can be paths, as in a multi-location update
+    let children = json!({ "day/0630": 11, "latest/data": { "0630": 11 } }).as_object().unwrap().clone();
+    tree::update(&mut root, "", children);
+    assert_eq!(tree::get(&root, "day/0630"), Some(&json!(11)));
+    assert_eq!(tree::get(&root, "latest"), Some(&json!({ "data": { "0630": 11 } })));
+}
+
+#[test]
+fn deleting_the_last_child_removes_the_parents() {
+    let mut root = json!({ "rs_data": { "data": { "a": 1, "b": { "c": 2 } } } });
+    tree::delete(&mut root, "rs_data/data/b/c");
+    assert_eq!(root, json!({ "rs_data": { "data": { "a": 1 } } }));
+    tree::delete(&mut root, "rs_data/data/missing");
+    assert_eq!(root, json!({ "rs_data": { "data": { "a": 1 } } }));
+
+    tree::delete(&mut root, "rs_data/data/a");
+    // The root itself stays an object
+    assert_eq!(root, json!({}));
+    tree::delete(&mut root, "");
+    assert_eq!(root, json!({}));
+}
+
+#[test]
+fn mostly_consecutive_integer_keys_become_arrays() {
+    assert_eq!(tree::coerce_arrays(&json!({ "0": "a", "1": "b" })), json!(["a", "b"]));
+    // More than half the indices present, so the gaps are nulls
+    assert_eq!(tree::coerce_arrays(&json!({ "0": "a", "2": "c" })), json!(["a", null, "c"]));
+    assert_eq!(tree::coerce_arrays(&json!({ "1": "b", "2": "c" })), json!([null, "b", "c"]));
+
+    // Too sparse
+    assert_eq!(tree::coerce_arrays(&json!({ "0": "a", "5": "f" })), json!({ "0": "a", "5": "f" }));
+    // Times are strings, not indices
+    assert_eq!(tree::coerce_arrays(&json!({ "0630": 1, "0700": 2 })), json!({ "0630": 1, "0700": 2 }));
+    assert_eq!(tree::coerce_arrays(&json!({ "0": 1, "a": 2 })), json!({ "0": 1, "a": 2 }));
+
+    // Applied all the way down
+    let week = json!({ "2023-10-02": { "0": { "0630": 12 }, "1": { "0630": 3 } } });
+    assert_eq!(tree::coerce_arrays(&week), json!({ "2023-10-02": [{ "0630": 12 }, { "0630": 3 }] }));
+    assert_eq!(tree::coerce_arrays(&json!(12)), json!(12));
+    assert_eq!(tree::coerce_arrays(&json!({})), Value::Object(Default::default()));
+}